@@ -1,9 +1,13 @@
 use super::ComtryaCommand;
-use crate::Runtime;
+use crate::{prompts, Runtime};
 use clap::Parser;
 use comfy_table::{Cell, ContentArrangement, Table};
+use comtrya_lib::atoms::Checkpoint;
+use comtrya_lib::backups::{self, BackupStore};
+use comtrya_lib::config::profile::FailurePolicy;
 use comtrya_lib::contexts::to_rhai;
-use comtrya_lib::manifests::{load, Manifest};
+use comtrya_lib::manifests::{declarations, load, Declarations, Manifest};
+use comtrya_lib::tera_functions::load_templates;
 use core::panic;
 use petgraph::{visit::DfsPostOrder, Graph};
 use rhai::Engine;
@@ -25,6 +29,14 @@ pub(crate) struct Apply {
     /// Define label selector
     #[arg(short, long)]
     pub label: Option<String>,
+
+    /// Apply a profile defined in Comtrya.yaml
+    #[arg(short, long)]
+    pub profile: Option<String>,
+
+    /// Fail instead of asking prompts that have no answer yet
+    #[arg(long)]
+    non_interactive: bool,
 }
 
 impl Apply {
@@ -56,6 +68,77 @@ impl Apply {
         Ok(manifest_path)
     }
 
+    /// Manifests given on the command line win over the ones of the profile,
+    /// which win over the ones enabled for this host
+    fn selected_manifests(&self, runtime: &Runtime) -> Vec<String> {
+        if !self.manifests.is_empty() {
+            return self.manifests.clone();
+        }
+
+        match (&runtime.profile, &runtime.config.host) {
+            (Some(profile), _) if !profile.manifests.is_empty() => profile.manifests.clone(),
+            (_, Some(host)) => host.manifests.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// A label given on the command line wins over the labels of the profile,
+    /// which win over the default labels of this host
+    fn selected_labels(&self, runtime: &Runtime) -> Vec<String> {
+        if let Some(label) = &self.label {
+            return vec![label.clone()];
+        }
+
+        match (&runtime.profile, &runtime.config.host) {
+            (Some(profile), _) if !profile.labels.is_empty() => profile.labels.clone(),
+            (_, Some(host)) => host.labels.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Names of the manifests that will run, whose prompts must be answered:
+    /// the selected ones with their dependencies, that have a selected label
+    fn prompted_manifests(
+        &self,
+        runtime: &Runtime,
+        manifests: &HashMap<String, Declarations>,
+    ) -> Vec<String> {
+        let selected = self.selected_manifests(runtime);
+        let labels = self.selected_labels(runtime);
+
+        let mut pending: Vec<String> = match selected.is_empty() {
+            true => manifests.keys().cloned().collect(),
+            false => selected,
+        };
+        let mut visited: Vec<String> = vec![];
+        let mut names: Vec<String> = vec![];
+
+        while let Some(name) = pending.pop() {
+            let Some(manifest) = manifests.get(&name) else {
+                continue;
+            };
+
+            if visited.contains(&name) {
+                continue;
+            }
+            visited.push(name.clone());
+
+            pending.extend(
+                manifest
+                    .depends
+                    .iter()
+                    .map(|dependency| resolve_dependency(&name, dependency)),
+            );
+
+            if labels.is_empty() || labels.iter().any(|label| manifest.labels.contains(label)) {
+                names.push(name);
+            }
+        }
+
+        names.sort();
+        names
+    }
+
     #[instrument(skip(self, runtime))]
     pub fn status(&self, runtime: &Runtime) -> anyhow::Result<()> {
         let contexts = &runtime.contexts;
@@ -63,6 +146,7 @@ impl Apply {
 
         println!("Load manifests from path: {:#?}", manifest_path);
 
+        load_templates(&runtime.config.templates_path(&manifest_path))?;
         let manifests = load(manifest_path, contexts);
 
         let mut table = Table::new();
@@ -82,12 +166,64 @@ impl Apply {
     }
 }
 
+/// Reverts the steps that ran, most recent first. Steps that can't be
+/// reverted are reported, as their changes stay in place.
+fn revert(checkpoints: Vec<(String, Option<Checkpoint>)>) {
+    for (atom, checkpoint) in checkpoints.into_iter().rev() {
+        match checkpoint {
+            Some(checkpoint) => match checkpoint.revert() {
+                Ok(()) => info!("Reverted: {}", atom),
+                Err(err) => error!("Failed to revert: {}: {:#}", atom, err),
+            },
+            None => warn!("Can't revert, its changes are kept: {}", atom),
+        }
+    }
+}
+
+/// Dependencies starting with `./` are relative to the manifest's directory
+fn resolve_dependency(name: &str, dependency: &str) -> String {
+    let (local_dependency_prefix, _) = name.rsplit_once('.').unwrap_or((name, ""));
+
+    dependency.replace("./", format!("{local_dependency_prefix}.").as_str())
+}
+
 impl ComtryaCommand for Apply {
     #[instrument(skip(self, runtime))]
     fn execute(&self, runtime: &Runtime) -> anyhow::Result<()> {
-        let contexts = &runtime.contexts;
+        let mut contexts = runtime.contexts.clone();
         let manifest_path = self.manifest_path(runtime)?;
-        let manifests = load(manifest_path, contexts);
+        load_templates(&runtime.config.templates_path(&manifest_path))?;
+
+        prompts::ask(&runtime.config.prompts, &mut contexts, self.non_interactive)?;
+
+        // Manifests can use the answers to their own prompts, so these are
+        // asked before the manifests are rendered
+        let declarations = declarations(manifest_path.clone(), &contexts)?;
+        let manifest_prompts = self.prompted_manifests(runtime, &declarations);
+        prompts::ask(
+            manifest_prompts
+                .iter()
+                .flat_map(|name| declarations[name].prompts.iter()),
+            &mut contexts,
+            self.non_interactive,
+        )?;
+
+        let manifests = load(manifest_path, &contexts);
+
+        let contexts = &contexts;
+
+        if !self.dry_run && runtime.config.backups.enabled {
+            if let Some(dir) = BackupStore::default_dir() {
+                let store = BackupStore::open(dir);
+
+                if let Err(err) = store.prune(&runtime.config.backups) {
+                    warn!("Failed to prune backups: {:#}", err);
+                }
+
+                debug!("Backing up changed files as run {}", store.run_id());
+                backups::init(store);
+            }
+        }
 
         // Build DAG
         let mut dag: Graph<Manifest, u32, petgraph::Directed> = Graph::new();
@@ -118,10 +254,7 @@ impl ComtryaCommand for Apply {
 
         for (name, manifest) in manifests.iter() {
             manifest.depends.iter().for_each(|dependency| {
-                let (local_dependency_prefix, _) = name.rsplit_once('.').unwrap_or((name, ""));
-
-                let resolved_dependency_name =
-                    dependency.replace("./", format!("{local_dependency_prefix}.").as_str());
+                let resolved_dependency_name = resolve_dependency(name, dependency);
 
                 let m1 = match manifests.get(&resolved_dependency_name) {
                     Some(manifest) => manifest,
@@ -149,9 +282,10 @@ impl ComtryaCommand for Apply {
             });
         }
 
-        let clone_m = self.manifests.clone();
+        let clone_m = self.selected_manifests(runtime);
+        let labels = self.selected_labels(runtime);
 
-        let run_manifests = if self.manifests.is_empty() {
+        let run_manifests = if clone_m.is_empty() {
             // No manifests specified on command line, so run everything
             vec![String::from("")]
         } else {
@@ -164,6 +298,11 @@ impl ComtryaCommand for Apply {
         };
 
         let dry_run = self.dry_run;
+        let on_failure = runtime
+            .profile
+            .as_ref()
+            .map(|profile| profile.on_failure)
+            .unwrap_or_default();
 
         let engine = Engine::new();
         let mut scope = to_rhai(contexts);
@@ -208,14 +347,12 @@ impl ComtryaCommand for Apply {
 
                 let mut successful = true;
 
-                if let Some(label) = self.label.as_ref() {
-                    if !m1.labels.contains(label) {
-                        info!(
-                            message = "Skipping manifest, label not found",
-                            label = label.as_str()
-                        );
-                        continue;
-                    }
+                if !labels.is_empty() && !labels.iter().any(|label| m1.labels.contains(label)) {
+                    info!(
+                        message = "Skipping manifest, label not found",
+                        label = labels.join(",").as_str()
+                    );
+                    continue;
                 }
 
                 if let Some(where_condition) = &m1.r#where {
@@ -242,7 +379,14 @@ impl ComtryaCommand for Apply {
                     }
                 }
 
+                // Atomic manifests undo what already ran when a step fails
+                let mut checkpoints: Vec<(String, Option<Checkpoint>)> = vec![];
+
                 for action in m1.actions.iter() {
+                    if m1.atomic && !successful {
+                        break;
+                    }
+
                     let span_action = span!(tracing::Level::INFO, "", %action).entered();
 
                     let action = action.inner_ref();
@@ -276,6 +420,22 @@ impl ComtryaCommand for Apply {
                             continue;
                         }
 
+                        if m1.atomic {
+                            match step.atom.checkpoint() {
+                                Ok(checkpoint) => {
+                                    checkpoints.push((step.atom.to_string(), checkpoint))
+                                }
+                                Err(err) => {
+                                    error!(
+                                        "Failed to capture state before {}: {:?}",
+                                        step.atom, err
+                                    );
+                                    successful = false;
+                                    break;
+                                }
+                            }
+                        }
+
                         match step.atom.execute() {
                             Ok(_) => (),
                             Err(err) => {
@@ -300,10 +460,18 @@ impl ComtryaCommand for Apply {
                     continue;
                 }
 
+                if !successful && m1.atomic {
+                    revert(checkpoints);
+                }
+
                 if !successful {
                     error!("Failed");
                     span_manifest.exit();
-                    break;
+
+                    match on_failure {
+                        FailurePolicy::Stop => break,
+                        FailurePolicy::Continue => continue,
+                    }
                 }
 
                 info!("Completed");
@@ -0,0 +1,142 @@
+use super::{format_age, ComtryaCommand};
+use crate::Runtime;
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use comfy_table::{presets::NOTHING, Attribute, Cell, ContentArrangement, Table};
+use comtrya_lib::backups::{BackupStore, EntryKind};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help = true)]
+pub(crate) struct Backups {
+    #[command(subcommand)]
+    command: BackupsSubCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupsSubCommands {
+    /// List runs that backed up files
+    List,
+    /// List the files backed up by a run, or print the backup of one file
+    Show {
+        /// Id of the run, or `latest`
+        run: String,
+        path: Option<PathBuf>,
+    },
+    /// Restore the files backed up by a run, or only the given paths
+    Restore {
+        /// Id of the run, or `latest`
+        run: String,
+        paths: Vec<PathBuf>,
+    },
+}
+
+impl ComtryaCommand for Backups {
+    fn execute(&self, _runtime: &Runtime) -> Result<()> {
+        let dir = BackupStore::default_dir()
+            .ok_or_else(|| anyhow!("There are no backups without a local data directory"))?;
+        let mut store = BackupStore::open(dir);
+
+        match &self.command {
+            BackupsSubCommands::List => {
+                let mut table = table();
+                table.set_header(vec!["Run", "Age", "Files"]);
+
+                for run in store.runs()?.iter().rev() {
+                    table.add_row(vec![
+                        Cell::new(&run.id).add_attribute(Attribute::Bold),
+                        Cell::new(format_started(run.started)),
+                        Cell::new(run.entries.len()),
+                    ]);
+                }
+
+                println!("{table}");
+            }
+            BackupsSubCommands::Show { run, path: None } => {
+                let run = store.run(run)?;
+                println!(
+                    "{} {}",
+                    run.id.underline().bold(),
+                    format_started(run.started)
+                );
+
+                let mut table = table();
+                for entry in run.entries.iter() {
+                    let state = match entry.kind {
+                        EntryKind::File => String::from("file"),
+                        EntryKind::Directory => String::from("directory"),
+                        EntryKind::Symlink => format!(
+                            "link to {}",
+                            entry.link_target.clone().unwrap_or_default().display()
+                        ),
+                    };
+
+                    table.add_row(vec![Cell::new(entry.path.display()), Cell::new(state)]);
+                }
+
+                println!("{table}");
+            }
+            BackupsSubCommands::Show {
+                run,
+                path: Some(path),
+            } => {
+                let run = store.run(run)?;
+                let entry = run
+                    .entry(&std::path::absolute(path)?)
+                    .ok_or_else(|| anyhow!("Run {} didn't back up {}", run.id, path.display()))?;
+
+                std::io::stdout().write_all(&store.contents(entry)?)?;
+            }
+            BackupsSubCommands::Restore { run, paths } => {
+                let run = store.run(run)?;
+
+                let paths = paths
+                    .iter()
+                    .map(std::path::absolute)
+                    .collect::<std::io::Result<Vec<PathBuf>>>()?;
+
+                if let Some(path) = paths.iter().find(|path| run.entry(path).is_none()) {
+                    return Err(anyhow!("Run {} didn't back up {}", run.id, path.display()));
+                }
+
+                // Restore removed directories before the files within them
+                for entry in run.entries.iter().rev() {
+                    if !paths.is_empty() && !paths.contains(&entry.path) {
+                        continue;
+                    }
+
+                    store.restore(entry)?;
+                    info!("Restored {}", entry.path.display());
+                }
+
+                if !store.current_run().entries.is_empty() {
+                    info!("Replaced files were backed up as run {}", store.run_id());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn table() -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(NOTHING)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    table
+}
+
+fn format_started(started: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    format!("{} ago", format_age(now.saturating_sub(started)))
+}
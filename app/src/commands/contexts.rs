@@ -1,9 +1,19 @@
-use super::ComtryaCommand;
+use super::{format_age, ComtryaCommand};
 use crate::Runtime;
+use anyhow::anyhow;
 use colored::Colorize;
 use comfy_table::{presets::NOTHING, Attribute, Cell, ContentArrangement, Table};
+use comtrya_lib::contexts::secrets::{redact, REDACTED};
+use comtrya_lib::contexts::to_rhai;
+use comtrya_lib::contexts::variable_include::cache::IncludeCache;
+use comtrya_lib::tera_functions;
+use comtrya_lib::values::Value;
+use rhai::{Dynamic, Engine};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command()]
@@ -11,11 +21,81 @@ pub(crate) struct Contexts {
     /// Show the values of the contexts
     #[arg(long)]
     show_values: bool,
+
+    /// Output format of the contexts, or of the value of `get`
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Option<ContextsSubCommands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum ContextsSubCommands {
+    /// Print a single context, or a value as `<context>.<key>`
+    Get { key: String },
+    /// Evaluate a rhai expression, e.g. a `where` condition
+    Eval { expression: String },
+    /// Render a Tera template
+    Render { template: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Yaml,
 }
 
 impl ComtryaCommand for Contexts {
     fn execute(&self, runtime: &Runtime) -> anyhow::Result<()> {
-        for (name, context) in runtime.contexts.iter() {
+        match &self.command {
+            Some(ContextsSubCommands::Get { key }) => self.get(runtime, key),
+            Some(ContextsSubCommands::Eval { expression }) => eval(runtime, expression),
+            Some(ContextsSubCommands::Render { template }) => render(runtime, template),
+            None if self.format != Format::Table => print(self.format, &redacted(runtime)),
+            None => self.print_tables(runtime),
+        }
+    }
+}
+
+impl Contexts {
+    fn get(&self, runtime: &Runtime, key: &str) -> anyhow::Result<()> {
+        let (name, key) = match key.split_once('.') {
+            Some((name, key)) => (name, Some(key)),
+            None => (key, None),
+        };
+
+        let contexts = redacted(runtime);
+        let context = contexts
+            .get(name)
+            .ok_or_else(|| anyhow!("Context '{name}' doesn't exist"))?;
+
+        let Some(key) = key else {
+            return match self.format {
+                Format::Table => {
+                    println!("{}", values_table(context));
+                    Ok(())
+                }
+                format => print(format, context),
+            };
+        };
+
+        let value = context
+            .get(key)
+            .ok_or_else(|| anyhow!("Key '{key}' doesn't exist in context '{name}'"))?;
+
+        match self.format {
+            Format::Table => {
+                println!("{value}");
+                Ok(())
+            }
+            format => print(format, value),
+        }
+    }
+
+    fn print_tables(&self, runtime: &Runtime) -> anyhow::Result<()> {
+        for (name, context) in redacted(runtime).iter() {
             println!("{}", name.to_string().underline().bold());
 
             let mut table = Table::new();
@@ -50,15 +130,7 @@ impl ComtryaCommand for Contexts {
                         table.add_row(row);
                     });
             } else {
-                for (key, value) in context.iter() {
-                    let value = strip_ansi_escapes::strip(value.to_string());
-                    let value = String::from_utf8(value).unwrap_or_default();
-
-                    table.add_row(vec![
-                        Cell::new(key).add_attribute(Attribute::Bold),
-                        Cell::new(value),
-                    ]);
-                }
+                table = values_table(context);
             }
 
             println!("{table}");
@@ -66,6 +138,123 @@ impl ComtryaCommand for Contexts {
             println!();
         }
 
+        self.print_include_cache(runtime);
+
         Ok(())
     }
 }
+
+/// Keys and their values, one per row
+fn values_table(context: &BTreeMap<String, Value>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(NOTHING)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    for (key, value) in context.iter() {
+        let value = strip_ansi_escapes::strip(value.to_string());
+        let value = String::from_utf8(value).unwrap_or_default();
+
+        table.add_row(vec![
+            Cell::new(key).add_attribute(Attribute::Bold),
+            Cell::new(value),
+        ]);
+    }
+
+    table
+}
+
+impl Contexts {
+    fn print_include_cache(&self, runtime: &Runtime) {
+        let (Some(includes), Some(cache)) =
+            (&runtime.config.include_variables, IncludeCache::new())
+        else {
+            return;
+        };
+
+        println!("{}", "include_variables cache".underline().bold());
+
+        let mut table = Table::new();
+        table
+            .load_preset(NOTHING)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+
+        for include in includes {
+            let spec = include.spec();
+
+            let age = if !spec.is_cacheable() {
+                String::from("not cached")
+            } else {
+                match cache.get(&spec) {
+                    Some(entry) => format!("{} old", format_age(entry.age().as_secs())),
+                    None => String::from("<empty>"),
+                }
+            };
+
+            table.add_row(vec![
+                Cell::new(spec.url).add_attribute(Attribute::Bold),
+                Cell::new(age),
+            ]);
+        }
+
+        println!("{table}");
+        println!();
+    }
+}
+
+/// The contexts with the values of secrets hidden
+fn redacted(runtime: &Runtime) -> comtrya_lib::contexts::Contexts {
+    let mut contexts = runtime.contexts.clone();
+
+    if let Some(secrets) = contexts.get_mut("secrets") {
+        secrets
+            .values_mut()
+            .for_each(|value| *value = REDACTED.into());
+    }
+
+    contexts
+}
+
+fn print<T: Serialize>(format: Format, value: &T) -> anyhow::Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Format::Table | Format::Yaml => print!("{}", serde_yaml_ng::to_string(value)?),
+    }
+
+    Ok(())
+}
+
+/// Evaluates against the same scope `where` conditions see
+fn eval(runtime: &Runtime, expression: &str) -> anyhow::Result<()> {
+    let engine = Engine::new();
+    let mut scope = to_rhai(&runtime.contexts);
+
+    let result = engine
+        .eval_with_scope::<Dynamic>(&mut scope, expression)
+        .map_err(|err| anyhow!("Failed to evaluate '{expression}': {err}"))?;
+
+    println!("{}", redact(&result.to_string()));
+
+    Ok(())
+}
+
+fn render(runtime: &Runtime, template: &str) -> anyhow::Result<()> {
+    // Shared templates of local manifests can be included as well, without
+    // cloning remote ones just to render
+    if let Some(manifest_path) = runtime
+        .config
+        .manifest_paths
+        .first()
+        .map(PathBuf::from)
+        .filter(|path| path.is_dir())
+    {
+        tera_functions::load_templates(&runtime.config.templates_path(&manifest_path))?;
+    }
+
+    println!(
+        "{}",
+        redact(&tera_functions::render(template, &runtime.contexts)?)
+    );
+
+    Ok(())
+}
@@ -7,6 +7,12 @@ pub(crate) use version::Version;
 mod contexts;
 pub(crate) use contexts::Contexts;
 
+mod backups;
+pub(crate) use backups::Backups;
+
+mod secrets;
+pub(crate) use secrets::Secrets;
+
 mod gen_completions;
 pub(crate) use gen_completions::GenCompletions;
 
@@ -15,3 +21,13 @@ use crate::Runtime;
 pub trait ComtryaCommand {
     fn execute(&self, runtime: &Runtime) -> anyhow::Result<()>;
 }
+
+/// A duration in seconds as its largest whole unit, like `5m`
+pub(crate) fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
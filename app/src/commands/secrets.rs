@@ -0,0 +1,212 @@
+use super::ComtryaCommand;
+use crate::Runtime;
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use comtrya_lib::encryption::{self, Encryption};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help = true)]
+pub(crate) struct Secrets {
+    /// Age recipients, SSH public keys or files listing them. Defaults to
+    /// `recipients` of Comtrya.yaml
+    #[arg(short, long, global = true)]
+    recipient: Vec<String>,
+
+    /// Age identity files or SSH private keys. Defaults to `identities` of
+    /// Comtrya.yaml
+    #[arg(short, long, global = true)]
+    identity: Vec<String>,
+
+    #[command(subcommand)]
+    command: SecretsSubCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretsSubCommands {
+    /// Encrypt a file to the recipients, written next to it as `<file>.age`
+    Encrypt {
+        file: PathBuf,
+
+        /// Where to write the encrypted file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Encrypt a value for `variables` in Comtrya.yaml, printed to stdout.
+    /// Reads the value from stdin when not given, keeping it out of the
+    /// shell history
+    EncryptValue { value: Option<String> },
+    /// Decrypt a file with the identities, printed to stdout
+    Decrypt {
+        file: PathBuf,
+
+        /// Where to write the decrypted file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Re-encrypt files to the current recipients, by default every
+    /// encrypted file in the `files` directories of the manifests
+    Rekey { files: Vec<PathBuf> },
+}
+
+impl ComtryaCommand for Secrets {
+    fn execute(&self, runtime: &Runtime) -> Result<()> {
+        match &self.command {
+            SecretsSubCommands::Encrypt { file, output } => {
+                let recipients = encryption::parse_recipients(self.recipients(runtime))?;
+
+                let plaintext = std::fs::read(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+
+                let output = output.clone().unwrap_or_else(|| {
+                    let mut output = file.clone().into_os_string();
+                    output.push(".age");
+                    output.into()
+                });
+
+                std::fs::write(&output, encryption::encrypt(&recipients, &plaintext)?)
+                    .with_context(|| format!("Failed to write {}", output.display()))?;
+
+                info!("Encrypted {} to {}", file.display(), output.display());
+
+                Ok(())
+            }
+            SecretsSubCommands::EncryptValue { value } => {
+                let recipients = encryption::parse_recipients(self.recipients(runtime))?;
+
+                let value = match value {
+                    Some(value) => value.clone(),
+                    None => {
+                        let mut value = String::new();
+                        std::io::stdin().read_to_string(&mut value)?;
+                        value.trim_end_matches(['\r', '\n']).to_string()
+                    }
+                };
+
+                std::io::stdout()
+                    .write_all(&encryption::encrypt(&recipients, value.as_bytes())?)?;
+
+                Ok(())
+            }
+            SecretsSubCommands::Decrypt { file, output } => {
+                let identities = encryption::load_identities(self.identities(runtime))?;
+
+                let plaintext = encryption::decrypt(&identities, &read(file)?)
+                    .with_context(|| format!("Failed to decrypt {}", file.display()))?;
+
+                match output {
+                    Some(output) => std::fs::write(output, plaintext)
+                        .with_context(|| format!("Failed to write {}", output.display()))?,
+                    None => std::io::stdout().write_all(&plaintext)?,
+                }
+
+                Ok(())
+            }
+            SecretsSubCommands::Rekey { files } => {
+                let identities = encryption::load_identities(self.identities(runtime))?;
+                let recipients = encryption::parse_recipients(self.recipients(runtime))?;
+
+                let files = match files.is_empty() {
+                    true => encrypted_files(runtime)?,
+                    false => files.clone(),
+                };
+
+                for file in files {
+                    let plaintext = encryption::decrypt(&identities, &read(&file)?)
+                        .with_context(|| format!("Failed to decrypt {}", file.display()))?;
+
+                    replace(&file, &encryption::encrypt(&recipients, &plaintext)?)
+                        .with_context(|| format!("Failed to write {}", file.display()))?;
+
+                    info!("Rekeyed {}", file.display());
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Secrets {
+    fn recipients<'a>(&'a self, runtime: &'a Runtime) -> &'a [String] {
+        match self.recipient.is_empty() {
+            true => &runtime.config.recipients,
+            false => &self.recipient,
+        }
+    }
+
+    fn identities<'a>(&'a self, runtime: &'a Runtime) -> &'a [String] {
+        match self.identity.is_empty() {
+            true => &runtime.config.identities,
+            false => &self.identity,
+        }
+    }
+}
+
+fn read(file: &Path) -> Result<Vec<u8>> {
+    std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))
+}
+
+/// Writes `contents` next to `file` and renames it over `file`, so a failed
+/// write never leaves it half rekeyed
+fn replace(file: &Path, contents: &[u8]) -> Result<()> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let partial = file.with_file_name(format!(".{name}.comtrya-partial"));
+
+    let written = std::fs::write(&partial, contents).and_then(|()| {
+        let permissions = std::fs::metadata(file)?.permissions();
+        std::fs::set_permissions(&partial, permissions)?;
+
+        std::fs::rename(&partial, file)
+    });
+
+    if written.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+
+    Ok(written?)
+}
+
+/// Files below a `files` directory of the manifest paths that are encrypted
+/// to recipients. Files encrypted with a passphrase are left alone.
+fn encrypted_files(runtime: &Runtime) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+
+    for manifest_path in runtime.config.manifest_paths.iter() {
+        let manifest_path = crate::manifests::resolve(manifest_path)
+            .ok_or_else(|| anyhow!("Manifest location, {manifest_path:?}, can't be resolved"))?;
+
+        for entry in WalkDir::new(&manifest_path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+        {
+            let path = entry.path();
+
+            let in_files_dir = path
+                .strip_prefix(&manifest_path)
+                .map(|relative| relative.components().any(|c| c.as_os_str() == "files"))
+                .unwrap_or(false);
+
+            if !in_files_dir {
+                continue;
+            }
+
+            match encryption::detect(&read(path)?) {
+                Some(Encryption::Recipients) => files.push(path.to_path_buf()),
+                Some(Encryption::Passphrase) => warn!(
+                    "Skipping {}, it's encrypted with a passphrase",
+                    path.display()
+                ),
+                None => (),
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
@@ -28,6 +28,10 @@ pub struct GlobalArgs {
     #[arg(short = 'D', long, value_parser = parse_key_val::<String, String>)]
     pub defines: Vec<(String, String)>,
 
+    /// Ignore cached variable includes and query their sources again
+    #[arg(long)]
+    pub refresh_includes: bool,
+
     /// Debug & tracing mode (-v, -vv)
     #[arg(short, action = clap::ArgAction::Count)]
     pub verbose: u8,
@@ -51,6 +55,12 @@ pub enum Commands {
     /// List available contexts
     Contexts(commands::Contexts),
 
+    /// Encrypt, decrypt and rekey encrypted files
+    Secrets(commands::Secrets),
+
+    /// List and restore files that were overwritten or removed
+    Backups(commands::Backups),
+
     /// Auto generate completions
     ///
     /// for examples:
@@ -60,6 +70,27 @@ pub enum Commands {
     GenCompletions(commands::GenCompletions),
 }
 
+impl GlobalArgs {
+    /// Commands that don't look at contexts skip running the context providers
+    pub(crate) fn needs_contexts(&self) -> bool {
+        !matches!(
+            self.command,
+            Commands::Version(_)
+                | Commands::GenCompletions(_)
+                | Commands::Secrets(_)
+                | Commands::Backups(_)
+        )
+    }
+
+    /// The profile requested on the command line, if any
+    pub(crate) fn profile(&self) -> Option<&str> {
+        match &self.command {
+            Commands::Apply(apply) | Commands::Status(apply) => apply.profile.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 impl Default for Commands {
     fn default() -> Self {
         Commands::Version(commands::Version {})
@@ -134,6 +165,17 @@ pub fn lib_config(args: &GlobalArgs) -> anyhow::Result<Config> {
                 }
             }
 
+            // Inventory paths are relative to the config file
+            config
+                .apply_inventory(config_path.parent().unwrap_or(Path::new(".")))
+                .with_context(|| "Found Comtrya.yaml, but couldn't resolve the inventory.")?;
+
+            // So is the secrets file
+            if let (Some(secrets_file), Some(parent)) = (&config.secrets_file, config_path.parent())
+            {
+                config.secrets_file = Some(parent.join(secrets_file).display().to_string());
+            }
+
             config
         }
 
@@ -152,6 +194,8 @@ pub fn lib_config(args: &GlobalArgs) -> anyhow::Result<Config> {
         }
     };
 
+    config.refresh_includes = args.refresh_includes;
+
     let defines_iterator = args.defines.iter();
     for pair in defines_iterator {
         config.variables.insert(pair.0.clone(), pair.1.clone());
@@ -3,18 +3,21 @@ use crate::config::{Commands, GlobalArgs};
 
 use std::io;
 
+use comtrya_lib::config::profile::Profile;
 use comtrya_lib::contexts::build_contexts;
 use comtrya_lib::contexts::Contexts;
 use comtrya_lib::manifests;
 
 use clap::Parser;
-use tracing::{error, Level};
+use tracing::{debug, error, Level};
 
 #[allow(unused_imports)]
 use tracing_subscriber::{fmt::writer::MakeWriterExt, layer::SubscriberExt, FmtSubscriber};
 
 mod commands;
 mod config;
+mod prompts;
+mod redact;
 use config::Config;
 
 #[derive(Debug)]
@@ -22,6 +25,7 @@ pub struct Runtime {
     pub(crate) args: GlobalArgs,
     pub(crate) config: Config,
     pub(crate) contexts: Contexts,
+    pub(crate) profile: Option<Profile>,
 }
 
 pub(crate) fn execute(runtime: Runtime) -> anyhow::Result<()> {
@@ -30,6 +34,8 @@ pub(crate) fn execute(runtime: Runtime) -> anyhow::Result<()> {
         Commands::Status(apply) => apply.status(&runtime),
         Commands::Version(version) => version.execute(&runtime),
         Commands::Contexts(contexts) => contexts.execute(&runtime),
+        Commands::Secrets(secrets) => secrets.execute(&runtime),
+        Commands::Backups(backups) => backups.execute(&runtime),
         Commands::GenCompletions(gen_completions) => gen_completions.execute(&runtime),
     }
 }
@@ -45,13 +51,16 @@ fn configure_tracing(args: &GlobalArgs) {
         .with_max_level(Level::TRACE)
         .with_ansi(!args.no_color)
         .with_target(false)
-        .with_writer(stdout_writer)
+        .with_writer(redact::Redacting(stdout_writer))
         .without_time();
 
+    // The journal formats events itself, so events with secrets are left out
     #[cfg(target_os = "linux")]
     if let Ok(layer) = tracing_journald::layer() {
-        tracing::subscriber::set_global_default(builder.finish().with(layer))
-            .expect("Unable to set a global subscriber");
+        tracing::subscriber::set_global_default(
+            builder.finish().with(redact::WithoutSecrets(layer)),
+        )
+        .expect("Unable to set a global subscriber");
         return;
     }
 
@@ -63,7 +72,7 @@ fn main() -> anyhow::Result<()> {
     let args = GlobalArgs::parse();
     configure_tracing(&args);
 
-    let config = match config::load_config(&args) {
+    let mut config = match config::load_config(&args) {
         Ok(config) => config,
         Err(error) => {
             error!("{}", error.to_string());
@@ -71,16 +80,55 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
+    comtrya_lib::http::init(config.http.clone())?;
+
     if !config.disable_update_check {
         check_for_updates(args.no_color);
     }
 
     // Run Context Providers
-    let contexts = build_contexts(&config);
+    let mut contexts = if args.needs_contexts() {
+        build_contexts(&config)
+    } else {
+        Contexts::default()
+    };
+
+    let hostname = contexts
+        .get("os")
+        .and_then(|os| os.get("hostname"))
+        .map(|hostname| hostname.to_string())
+        .unwrap_or_default();
+
+    // Profiles are chosen by their conditions on contexts, so commands that
+    // don't build contexts don't use a profile either
+    let selected = match args.needs_contexts() {
+        true => config.select_profile(args.profile(), &hostname, &contexts)?,
+        false => None,
+    };
+
+    let profile = match selected {
+        Some((name, profile)) => {
+            debug!("Using profile {}", name);
+            config.apply_profile(&profile, &mut contexts);
+
+            // Variables defined on the command line still take precedence
+            if let Some(variables) = contexts.get_mut("variables") {
+                for (key, value) in args.defines.iter() {
+                    config.variables.insert(key.clone(), value.clone());
+                    variables.insert(key.clone(), value.clone().into());
+                }
+            }
+
+            Some(profile)
+        }
+        None => None,
+    };
+
     let runtime = Runtime {
         args,
         config,
         contexts,
+        profile,
     };
 
     execute(runtime)?;
@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Context, Result};
+use comtrya_lib::contexts::prompts::{typed, Prompt, PromptStore, PromptType};
+use comtrya_lib::contexts::secrets::register_secret;
+use comtrya_lib::contexts::Contexts;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
+use std::io::IsTerminal;
+use tracing::debug;
+
+/// Asks every prompt that has no stored answer yet, stores the answers and
+/// adds them to the `prompts` context. Without a terminal, or when
+/// `non_interactive` is set, defaults are used and prompts without one fail.
+pub(crate) fn ask<'a>(
+    prompts: impl IntoIterator<Item = (&'a String, &'a Prompt)>,
+    contexts: &mut Contexts,
+    non_interactive: bool,
+) -> Result<()> {
+    let answered = contexts.entry(String::from("prompts")).or_default();
+
+    let missing: Vec<(&String, &Prompt)> = prompts
+        .into_iter()
+        .filter(|(name, _)| !answered.contains_key(*name))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if non_interactive || !std::io::stdin().is_terminal() {
+        let unanswered: Vec<&str> = missing
+            .iter()
+            .filter(|(_, prompt)| prompt.default.is_none())
+            .map(|(name, _)| name.as_str())
+            .collect();
+
+        if !unanswered.is_empty() {
+            return Err(anyhow!(
+                "Prompts without an answer: {}. Run interactively to answer them",
+                unanswered.join(", ")
+            ));
+        }
+
+        // Defaults aren't stored, so an interactive run still asks
+        for (name, prompt) in missing {
+            let default = prompt.default.as_deref().unwrap_or_default();
+            let answer = prompt
+                .validate(default)
+                .with_context(|| format!("Invalid default of prompt '{name}'"))?;
+
+            debug!("Using default of prompt {}", name);
+            answered.insert(name.clone(), typed(prompt.kind, answer));
+        }
+
+        return Ok(());
+    }
+
+    let mut store = PromptStore::new()
+        .ok_or_else(|| anyhow!("Can't store prompt answers without a local data directory"))??;
+
+    for (name, prompt) in missing {
+        let answer = ask_one(name, prompt)?;
+
+        if prompt.secret {
+            register_secret(&answer);
+        }
+
+        store.set(name, prompt, &answer)?;
+        answered.insert(name.clone(), typed(prompt.kind, answer));
+    }
+
+    store.save()?;
+
+    Ok(())
+}
+
+fn ask_one(name: &str, prompt: &Prompt) -> Result<String> {
+    let theme = ColorfulTheme::default();
+    let message = prompt.message.as_deref().unwrap_or(name);
+
+    if !prompt.choices.is_empty() {
+        let default = prompt
+            .default
+            .as_ref()
+            .and_then(|default| prompt.choices.iter().position(|choice| choice == default))
+            .unwrap_or(0);
+
+        let selected = Select::with_theme(&theme)
+            .with_prompt(message)
+            .items(&prompt.choices)
+            .default(default)
+            .interact()?;
+
+        return prompt.validate(&prompt.choices[selected]);
+    }
+
+    if prompt.secret {
+        return loop {
+            let answer = Password::with_theme(&theme)
+                .with_prompt(message)
+                .allow_empty_password(prompt.default.is_some())
+                .interact()?;
+
+            let answer = match (answer.is_empty(), &prompt.default) {
+                (true, Some(default)) => default.clone(),
+                _ => answer,
+            };
+
+            match prompt.validate(&answer) {
+                Ok(answer) => break Ok(answer),
+                Err(err) => eprintln!("{err}"),
+            }
+        };
+    }
+
+    if prompt.kind == PromptType::Boolean {
+        let mut confirm = Confirm::with_theme(&theme).with_prompt(message);
+
+        if let Some(default) = &prompt.default {
+            confirm = confirm.default(prompt.validate(default)? == "true");
+        }
+
+        return Ok(confirm.interact()?.to_string());
+    }
+
+    let mut input = Input::<String>::with_theme(&theme)
+        .with_prompt(message)
+        .validate_with(|answer: &String| prompt.validate(answer).map(|_| ()));
+
+    if let Some(default) = &prompt.default {
+        input = input.default(default.clone());
+    }
+
+    prompt.validate(&input.interact_text()?)
+}
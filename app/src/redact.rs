@@ -0,0 +1,186 @@
+use comtrya_lib::contexts::secrets::redact;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Dispatch, Event, Metadata, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Replaces secret values in everything written by `M`
+pub(crate) struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        RedactingWriter(self.0.make_writer_for(meta))
+    }
+}
+
+/// Events are written whole, so secrets aren't split across writes
+pub(crate) struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write_all(redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Keeps events that contain secret values, in their fields or the fields of
+/// their spans, from reaching `L`. For layers like journald that format
+/// events themselves, bypassing [`Redacting`].
+pub(crate) struct WithoutSecrets<L>(pub L);
+
+/// Field values of a span, checked for secrets with every event in it
+struct SpanValues(String);
+
+#[derive(Default)]
+struct Values(String);
+
+impl Visit for Values {
+    fn record_str(&mut self, _field: &Field, value: &str) {
+        self.0.push_str(value);
+        self.0.push('\n');
+    }
+
+    fn record_debug(&mut self, _field: &Field, value: &dyn fmt::Debug) {
+        let _ = writeln!(self.0, "{value:?}");
+    }
+}
+
+impl<S, L> Layer<S> for WithoutSecrets<L>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    L: Layer<S>,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.0.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.0.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.0.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.0.enabled(metadata, ctx)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut values = Values::default();
+            attrs.record(&mut values);
+            span.extensions_mut().insert(SpanValues(values.0));
+        }
+
+        self.0.on_new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, id: &Id, record: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut values = Values::default();
+            record.record(&mut values);
+
+            if let Some(SpanValues(recorded)) = span.extensions_mut().get_mut::<SpanValues>() {
+                recorded.push_str(&values.0);
+            }
+        }
+
+        self.0.on_record(id, record, ctx);
+    }
+
+    fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.0.on_follows_from(span, follows, ctx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut values = Values::default();
+        event.record(&mut values);
+
+        for span in ctx.event_scope(event).into_iter().flatten() {
+            if let Some(SpanValues(recorded)) = span.extensions().get::<SpanValues>() {
+                values.0.push_str(recorded);
+            }
+        }
+
+        if redact(&values.0) == values.0 {
+            self.0.on_event(event, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.0.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.0.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.0.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.0.on_id_change(old, new, ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use comtrya_lib::contexts::secrets::register_secret;
+    use std::sync::{Arc, Mutex};
+    use tracing::{info, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Records the messages of every event
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for Recorder {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut values = Values::default();
+            event.record(&mut values);
+            self.0.lock().unwrap().push(values.0);
+        }
+    }
+
+    #[test]
+    fn it_keeps_secrets_from_layers() {
+        register_secret("Tok'ra symbiote");
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let subscriber =
+            tracing_subscriber::registry().with(WithoutSecrets(Recorder(events.clone())));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("Chevron seven locked");
+            info!(host = "Tok'ra symbiote", "Blended");
+
+            let span = info_span!("blending", host = "Tok'ra symbiote");
+            let _entered = span.enter();
+            info!("Selmak");
+        });
+
+        assert_eq!(
+            vec![String::from("Chevron seven locked\n")],
+            *events.lock().unwrap()
+        );
+    }
+}
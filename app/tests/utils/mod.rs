@@ -11,7 +11,7 @@ use std::path::PathBuf;
 
 pub(crate) struct Dir {
     cwd: PathBuf,
-    env: String,
+    env: Vec<(String, PathBuf)>,
 }
 
 impl Dir {
@@ -19,6 +19,7 @@ impl Dir {
         let mut comtrya = Command::new(assert_cmd::cargo::cargo_bin!("comtrya"));
 
         comtrya.current_dir(self.cwd);
+        comtrya.envs(self.env);
 
         let args = cli.split(' ').collect::<Vec<_>>();
         comtrya.args(args);
@@ -26,8 +27,8 @@ impl Dir {
         comtrya.assert()
     }
 
-    pub fn env<S: Into<String>>(mut self, env: S) -> Dir {
-        self.env = env.into();
+    pub fn env<S: Into<String>, P: Into<PathBuf>>(mut self, key: S, value: P) -> Dir {
+        self.env.push((key.into(), value.into()));
 
         self
     }
@@ -44,7 +45,7 @@ pub(crate) fn cd(path: PathBuf) -> Dir {
 
     Dir {
         cwd: path,
-        env: "".into(),
+        env: vec![],
     }
 }
 
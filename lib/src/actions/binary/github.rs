@@ -2,13 +2,14 @@ use crate::actions::Action;
 use crate::atoms::file::Chmod;
 use crate::atoms::http::Download;
 use crate::contexts::Contexts;
+use crate::http::{self, Request, RequestOptions, Value};
 use crate::manifests::Manifest;
 use crate::steps::Step;
 use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use tokio::runtime::Runtime;
 use tracing::debug;
 
 #[derive(Clone, Debug, Default, JsonSchema, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,6 +25,57 @@ struct GitHubAsset {
     pub score: i32,
 }
 
+/// The assets of a release, as returned by the GitHub API
+#[derive(Deserialize)]
+struct Release {
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl BinaryGitHub {
+    fn release_url(&self) -> anyhow::Result<String> {
+        let (owner, repo) = self.repository.split_once('/').ok_or_else(|| {
+            anyhow!(
+                "Failed to parse repository name: {}",
+                self.repository.as_str()
+            )
+        })?;
+
+        let release = match self.version.as_deref() {
+            None | Some("latest") => String::from("latest"),
+            Some(version) => format!("tags/{version}"),
+        };
+
+        Ok(format!(
+            "https://api.github.com/repos/{owner}/{repo}/releases/{release}"
+        ))
+    }
+
+    /// Looks the release up through the shared HTTP client, so its
+    /// configuration, like a token for api.github.com, applies
+    fn release(&self) -> anyhow::Result<Release> {
+        let request = Request {
+            options: RequestOptions {
+                headers: BTreeMap::from([(
+                    String::from("Accept"),
+                    Value::Plain(String::from("application/vnd.github+json")),
+                )]),
+                auth: None,
+            },
+            ..Default::default()
+        };
+
+        let response = http::get(&self.release_url()?, &request)?;
+
+        Ok(serde_json::from_str(&response.text()?)?)
+    }
+}
+
 impl Action for BinaryGitHub {
     fn summarize(&self) -> String {
         format!(
@@ -38,32 +90,7 @@ impl Action for BinaryGitHub {
             return Ok(vec![]);
         };
 
-        let async_runtime = match Runtime::new() {
-            Ok(runtime) => runtime,
-            Err(e) => {
-                return Err(anyhow!("Failed to create async runtime: {e}"));
-            }
-        };
-
-        let (owner, repo) = self.repository.split_once('/').ok_or_else(|| {
-            anyhow!(
-                "Failed to parse repository name: {}",
-                self.repository.as_str()
-            )
-        })?;
-
-        let octocrab = async_runtime.block_on(async { octocrab::instance() });
-
-        let repos = octocrab.repos(owner, repo);
-        let releases = repos.releases();
-
-        let result = match &self.version {
-            Some(version) if version == "latest" => async_runtime.block_on(releases.get_latest()),
-            Some(version) => async_runtime.block_on(releases.get_by_tag(version.as_str())),
-            None => async_runtime.block_on(releases.get_latest()),
-        };
-
-        let release = match result {
+        let release = match self.release() {
             Ok(release) => release,
             Err(e) => {
                 return Err(anyhow!("Failed to find a release: {e}"));
@@ -109,7 +136,7 @@ impl Action for BinaryGitHub {
                 Some(ass) => {
                     if score > ass.score {
                         Some(GitHubAsset {
-                            url: asset.browser_download_url.into(),
+                            url: asset.browser_download_url,
                             score,
                         })
                     } else {
@@ -117,7 +144,7 @@ impl Action for BinaryGitHub {
                     }
                 }
                 None => Some(GitHubAsset {
-                    url: asset.browser_download_url.into(),
+                    url: asset.browser_download_url,
                     score,
                 }),
             }
@@ -135,10 +162,10 @@ impl Action for BinaryGitHub {
 
         Ok(vec![
             Step {
-                atom: Box::new(Download {
-                    url: asset.url,
-                    to: PathBuf::from(format!("{}/{}", self.directory, self.name)),
-                }),
+                atom: Box::new(Download::new(
+                    asset.url,
+                    PathBuf::from(format!("{}/{}", self.directory, self.name)),
+                )),
                 initializers: vec![],
                 finalizers: vec![],
             },
@@ -153,3 +180,32 @@ impl Action for BinaryGitHub {
         ])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_looks_up_releases_by_version() -> anyhow::Result<()> {
+        let mut action = BinaryGitHub {
+            repository: String::from("comtrya/comtrya"),
+            ..Default::default()
+        };
+        assert_eq!(
+            "https://api.github.com/repos/comtrya/comtrya/releases/latest",
+            action.release_url()?
+        );
+
+        action.version = Some(String::from("v0.9.2"));
+        assert_eq!(
+            "https://api.github.com/repos/comtrya/comtrya/releases/tags/v0.9.2",
+            action.release_url()?
+        );
+
+        action.repository = String::from("comtrya");
+        assert!(action.release_url().is_err());
+
+        Ok(())
+    }
+}
@@ -1,75 +1,198 @@
 use super::DirectoryAction;
-use crate::actions::Action;
+use crate::actions::file::from_octal_option;
+use crate::actions::{glob_set, Action};
+use crate::atoms::directory::{Create as DirCreate, Remove as DirRemove};
+#[cfg(unix)]
+use crate::atoms::file::Chown;
+use crate::atoms::file::{Chmod, Remove, SetContents};
 use crate::contexts::Contexts;
+use crate::manifests::Manifest;
 use crate::steps::Step;
-use crate::{atoms::command::Exec, manifests::Manifest};
+use crate::tera_functions::render;
+use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DirectoryCopy {
     pub from: String,
     pub to: String,
+
+    /// Renders every file as a Tera template
+    #[serde(default)]
+    pub template: bool,
+
+    /// Leaves out paths matching these globs, relative to `from`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Permissions of the copied files, instead of those of the source files
+    #[serde(default, deserialize_with = "from_octal_option")]
+    pub chmod: Option<u32>,
+
+    #[serde(rename = "owned_by_user")]
+    pub owner_user: Option<String>,
+
+    #[serde(rename = "owned_by_group")]
+    pub owner_group: Option<String>,
+
+    /// Removes files and directories of `to` that aren't in `from`
+    #[serde(default)]
+    pub sync: bool,
 }
 
-impl DirectoryCopy {}
+impl DirectoryCopy {
+    /// Copies `from` into `to`, or its contents when `to` ends with a slash
+    fn destination(&self, from: &Path) -> PathBuf {
+        let to = PathBuf::from(&self.to);
 
-impl DirectoryAction for DirectoryCopy {}
+        match self.to.ends_with(['/', '\\']) {
+            true => to,
+            false => to.join(from.file_name().unwrap_or_default()),
+        }
+    }
 
-#[cfg(target_family = "windows")]
-impl Action for DirectoryCopy {
-    fn summarize(&self) -> String {
-        format!("Copying {} to {}", self.from, self.to)
+    fn contents(&self, path: &Path, context: &Contexts) -> anyhow::Result<Vec<u8>> {
+        let contents = std::fs::read(path)
+            .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))?;
+
+        if !self.template {
+            return Ok(contents);
+        }
+
+        // Binary files are copied as they are
+        match std::str::from_utf8(&contents) {
+            Ok(text) => Ok(render(text, context)
+                .map_err(|err| anyhow!("Failed to render {}: {}", path.display(), err))?
+                .into_bytes()),
+            Err(_) => Ok(contents),
+        }
     }
 
-    fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let from: String = self.resolve(manifest, &self.from).display().to_string();
+    fn mode(&self, metadata: &std::fs::Metadata) -> u32 {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+
+            metadata.permissions().mode() & 0o7777
+        };
+
+        #[cfg(not(unix))]
+        let mode = {
+            let _ = metadata;
+            0o644
+        };
 
-        Ok(vec![Step {
-            atom: Box::new(Exec {
-                command: String::from("Xcopy"),
-                arguments: vec!["/E".to_string(), "/I".to_string(), from, self.to.clone()],
-                ..Default::default()
+        self.chmod.unwrap_or(mode)
+    }
+
+    #[cfg(unix)]
+    fn chown(&self, path: &Path) -> Option<Step> {
+        match (&self.owner_user, &self.owner_group) {
+            (Some(user), Some(group)) => Some(Step {
+                atom: Box::new(Chown {
+                    path: path.to_path_buf(),
+                    owner: user.clone(),
+                    group: group.clone(),
+                }),
+                initializers: vec![],
+                finalizers: vec![],
             }),
-            initializers: vec![],
-            finalizers: vec![],
-        }])
+            _ => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn chown(&self, _path: &Path) -> Option<Step> {
+        None
     }
 }
 
-#[cfg(target_family = "unix")]
+fn step(atom: impl crate::atoms::Atom + 'static) -> Step {
+    Step {
+        atom: Box::new(atom),
+        initializers: vec![],
+        finalizers: vec![],
+    }
+}
+
+impl DirectoryAction for DirectoryCopy {}
+
 impl Action for DirectoryCopy {
     fn summarize(&self) -> String {
         format!("Copying {} to {}", self.from, self.to)
     }
 
-    fn plan(&self, manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let mut from: String = self.resolve(manifest, &self.from).display().to_string();
+    fn plan(&self, manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>> {
+        let from = self.resolve(manifest, &self.from);
+        let to = self.destination(&from);
+        let exclude = glob_set(&self.exclude, "directory.copy")?;
+
+        let mut steps = vec![];
+        let mut copied = BTreeSet::new();
+
+        let entries = WalkDir::new(&from)
+            .follow_links(true)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(&from)
+                    .map(|relative| !exclude.is_match(relative))
+                    .unwrap_or(true)
+            });
+
+        for entry in entries {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(&from)?;
+            let path = to.join(relative);
 
-        if self.to.ends_with("/") {
-            from += "/."
+            copied.insert(relative.to_path_buf());
+
+            if entry.file_type().is_dir() {
+                steps.push(step(DirCreate { path: path.clone() }));
+            } else {
+                steps.push(step(SetContents {
+                    path: path.clone(),
+                    contents: self.contents(entry.path(), context)?,
+                }));
+                steps.push(step(Chmod {
+                    path: path.clone(),
+                    mode: self.mode(&entry.metadata()?),
+                }));
+            }
+
+            steps.extend(self.chown(&path));
         }
 
-        Ok(vec![
-            Step {
-                atom: Box::new(Exec {
-                    command: String::from("mkdir"),
-                    arguments: vec![String::from("-p"), self.to.clone()],
-                    ..Default::default()
-                }),
-                initializers: vec![],
-                finalizers: vec![],
-            },
-            Step {
-                atom: Box::new(Exec {
-                    command: String::from("cp"),
-                    arguments: vec![String::from("-r"), from, self.to.clone()],
-                    ..Default::default()
-                }),
-                initializers: vec![],
-                finalizers: vec![],
-            },
-        ])
+        if self.sync && to.is_dir() {
+            // Contents first, so directories are empty by the time they're removed
+            for entry in WalkDir::new(&to).min_depth(1).contents_first(true) {
+                let entry = entry?;
+                let relative = entry.path().strip_prefix(&to)?;
+
+                let excluded = relative
+                    .ancestors()
+                    .any(|path| !path.as_os_str().is_empty() && exclude.is_match(path));
+
+                if excluded || copied.contains(relative) {
+                    continue;
+                }
+
+                let target = entry.path().to_path_buf();
+
+                match entry.file_type().is_dir() {
+                    true => steps.push(step(DirRemove { target })),
+                    false => steps.push(step(Remove { target })),
+                }
+            }
+        }
+
+        Ok(steps)
     }
 }
 
@@ -104,4 +227,80 @@ mod tests {
             }
         };
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_copies_only_changes_and_syncs() -> anyhow::Result<()> {
+        use super::DirectoryCopy;
+        use crate::actions::Action;
+        use crate::config::Config;
+        use crate::contexts::build_contexts;
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir()?;
+        let from = root.path().join("files").join("nvim");
+        let to = root.path().join("config");
+
+        std::fs::create_dir_all(from.join("lua"))?;
+        std::fs::create_dir_all(from.join("cache"))?;
+        std::fs::write(from.join("init.lua"), "-- {{ 1 + 1 }}")?;
+        std::fs::write(from.join("lua/plugins.lua"), "return {}")?;
+        std::fs::write(from.join("cache/state"), "")?;
+        std::fs::write(from.join("run.sh"), "#!/bin/sh")?;
+        std::fs::set_permissions(from.join("run.sh"), std::fs::Permissions::from_mode(0o755))?;
+
+        std::fs::create_dir_all(to.join("nvim/old"))?;
+        std::fs::write(to.join("nvim/old/removed.lua"), "")?;
+
+        let manifest = Manifest {
+            root_dir: Some(root.path().to_path_buf()),
+            ..Default::default()
+        };
+        let contexts = build_contexts(&Config::default());
+
+        let action = DirectoryCopy {
+            from: String::from("nvim"),
+            to: to.display().to_string(),
+            template: true,
+            exclude: vec![String::from("cache")],
+            sync: true,
+            ..Default::default()
+        };
+
+        let apply = || -> anyhow::Result<usize> {
+            let mut ran = 0;
+
+            for mut step in action.plan(&manifest, &contexts)? {
+                if step.atom.plan()?.should_run {
+                    step.atom.execute()?;
+                    ran += 1;
+                }
+            }
+
+            Ok(ran)
+        };
+
+        assert!(apply()? > 0);
+
+        let copied = to.join("nvim");
+        assert_eq!("-- 2", std::fs::read_to_string(copied.join("init.lua"))?);
+        assert_eq!(
+            "return {}",
+            std::fs::read_to_string(copied.join("lua/plugins.lua"))?
+        );
+        assert_eq!(
+            0o755,
+            std::fs::metadata(copied.join("run.sh"))?
+                .permissions()
+                .mode()
+                & 0o777
+        );
+        assert!(!copied.join("cache").exists());
+        assert!(!copied.join("old").exists());
+
+        // Nothing changed, so nothing runs
+        assert_eq!(0, apply()?);
+
+        Ok(())
+    }
 }
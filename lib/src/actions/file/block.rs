@@ -0,0 +1,224 @@
+use super::line::{edit_steps, insert_position, join_lines, same_line, split_lines, State};
+use super::FileAction;
+use crate::actions::Action;
+use crate::atoms::file::ContentEdit;
+use crate::contexts::Contexts;
+use crate::manifests::Manifest;
+use crate::steps::Step;
+use anyhow::anyhow;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileBlock {
+    pub target: String,
+
+    #[serde(default)]
+    pub block: String,
+
+    /// Delimits the block, with `{mark}` replaced by BEGIN and END. Files
+    /// with more than one block need a different marker for each
+    #[serde(default = "default_marker")]
+    pub marker: String,
+
+    #[serde(default)]
+    pub state: State,
+
+    /// Where a missing block goes, after the last line matching this
+    pub insert_after: Option<String>,
+
+    /// Where a missing block goes, before the first line matching this
+    pub insert_before: Option<String>,
+
+    /// Create the file when it doesn't exist
+    #[serde(default)]
+    pub create: bool,
+}
+
+fn default_marker() -> String {
+    String::from("# {mark} COMTRYA MANAGED BLOCK")
+}
+
+impl FileBlock {
+    fn markers(&self) -> anyhow::Result<(String, String)> {
+        if !self.marker.contains("{mark}") {
+            return Err(anyhow!(
+                "file.block marker {:?} needs {{mark}}, to tell where the block begins and ends",
+                self.marker
+            ));
+        }
+
+        Ok((
+            self.marker.replace("{mark}", "BEGIN"),
+            self.marker.replace("{mark}", "END"),
+        ))
+    }
+}
+
+impl ContentEdit for FileBlock {
+    fn apply(&self, contents: &str) -> anyhow::Result<String> {
+        let (mut lines, trailing_newline) = split_lines(contents);
+        let (begin, end) = self.markers()?;
+
+        let is_end = |line: &String| same_line(line, &end);
+
+        // Splicing around a lone marker would take the user's lines with it
+        let existing = match lines.iter().position(|line| same_line(line, &begin)) {
+            Some(first) if lines[..first].iter().any(is_end) => {
+                return Err(anyhow!(
+                    "{:?} comes before {:?}, fix the markers by hand",
+                    end,
+                    begin
+                ));
+            }
+            Some(first) => match lines[first + 1..].iter().position(is_end) {
+                Some(last) => Some(first..=first + 1 + last),
+                None => {
+                    return Err(anyhow!(
+                        "{:?} has no {:?} after it, fix the markers by hand",
+                        begin,
+                        end
+                    ));
+                }
+            },
+            None if lines.iter().any(is_end) => {
+                return Err(anyhow!(
+                    "{:?} has no {:?} before it, fix the markers by hand",
+                    end,
+                    begin
+                ));
+            }
+            None => None,
+        };
+
+        let block: Vec<String> = match self.state {
+            State::Present => std::iter::once(begin)
+                .chain(self.block.lines().map(String::from))
+                .chain(std::iter::once(end))
+                .collect(),
+            State::Absent => vec![],
+        };
+
+        match existing {
+            Some(range) => {
+                lines.splice(range, block);
+            }
+            None if self.state == State::Present => {
+                let index = insert_position(&lines, &self.insert_after, &self.insert_before)?;
+                lines.splice(index..index, block);
+            }
+            None => (),
+        }
+
+        Ok(join_lines(&lines, trailing_newline))
+    }
+}
+
+impl std::fmt::Display for FileBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.state {
+            State::Present => write!(f, "the block {:?}", self.marker),
+            State::Absent => write!(f, "the block {:?} removed", self.marker),
+        }
+    }
+}
+
+impl FileAction for FileBlock {}
+
+impl Action for FileBlock {
+    fn summarize(&self) -> String {
+        format!("Editing a block of {}", self.target)
+    }
+
+    fn plan(&self, _: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
+        // Fail early on invalid options, rather than when applying
+        self.apply("")?;
+
+        Ok(edit_steps(
+            PathBuf::from(&self.target),
+            Box::new(self.clone()),
+            self.create,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Actions;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_be_deserialized() {
+        let yaml = r#"
+- action: file.block
+  target: ~/.profile
+  block: |
+    export EDITOR=nvim
+"#;
+
+        let mut actions: Vec<Actions> = serde_yaml_ng::from_str(yaml).unwrap();
+
+        match actions.pop() {
+            Some(Actions::FileBlock(action)) => {
+                assert_eq!("~/.profile", action.action.target);
+                assert_eq!("export EDITOR=nvim\n", action.action.block);
+                assert_eq!("# {mark} COMTRYA MANAGED BLOCK", action.action.marker);
+            }
+            _ => {
+                panic!("FileBlock didn't deserialize to the correct type");
+            }
+        };
+    }
+
+    #[test]
+    fn it_edits_blocks() -> anyhow::Result<()> {
+        let profile = "export LANG=C\n";
+
+        let mut block = FileBlock {
+            block: String::from("export EDITOR=nvim\nexport PAGER=less\n"),
+            marker: default_marker(),
+            ..Default::default()
+        };
+
+        let added = block.apply(profile)?;
+        assert_eq!(
+            "export LANG=C\n# BEGIN COMTRYA MANAGED BLOCK\nexport EDITOR=nvim\nexport PAGER=less\n# END COMTRYA MANAGED BLOCK\n",
+            added
+        );
+        assert_eq!(added, block.apply(&added)?);
+
+        block.block = String::from("export EDITOR=hx\n");
+        block.insert_before = Some(String::from("^export LANG"));
+        assert_eq!(
+            "export LANG=C\n# BEGIN COMTRYA MANAGED BLOCK\nexport EDITOR=hx\n# END COMTRYA MANAGED BLOCK\n",
+            block.apply(&added)?
+        );
+
+        block.state = State::Absent;
+        assert_eq!(profile, block.apply(&added)?);
+
+        block.marker = String::from("# COMTRYA");
+        assert!(block.apply(profile).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_refuses_orphaned_markers() {
+        let block = FileBlock {
+            block: String::from("export EDITOR=nvim\n"),
+            marker: default_marker(),
+            ..Default::default()
+        };
+
+        for profile in [
+            "# BEGIN COMTRYA MANAGED BLOCK\nexport EDITOR=vi\nexport LANG=C\n",
+            "export LANG=C\n# END COMTRYA MANAGED BLOCK\n",
+            "# END COMTRYA MANAGED BLOCK\n# BEGIN COMTRYA MANAGED BLOCK\nexport LANG=C\n# END COMTRYA MANAGED BLOCK\n",
+        ] {
+            assert!(block.apply(profile).is_err(), "{profile}");
+        }
+    }
+}
@@ -0,0 +1,1179 @@
+use super::line::{edit_steps, join_lines, split_lines};
+use super::FileAction;
+use crate::actions::Action;
+use crate::atoms::file::ContentEdit;
+use crate::contexts::Contexts;
+use crate::manifests::Manifest;
+use crate::steps::Step;
+use anyhow::{anyhow, Context};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+#[derive(JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+    Ini,
+}
+
+#[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileConfig {
+    pub target: String,
+
+    /// Defaults to the format of the file extension
+    pub format: Option<ConfigFormat>,
+
+    /// Deep merged into the document, before setting and deleting keys
+    pub merge: Option<serde_json::Value>,
+
+    /// Values by key path, with keys separated by dots. Dots within a key
+    /// are escaped as `\.`
+    #[serde(default)]
+    pub set: BTreeMap<String, serde_json::Value>,
+
+    /// Key paths to remove
+    #[serde(default)]
+    pub delete: Vec<String>,
+
+    /// Create the file when it doesn't exist
+    #[serde(default)]
+    pub create: bool,
+}
+
+impl FileConfig {
+    fn format(&self) -> anyhow::Result<ConfigFormat> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+
+        let path = Path::new(&self.target);
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        match extension {
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            "ini" | "cfg" | "conf" | "gitconfig" => Ok(ConfigFormat::Ini),
+            _ if path.file_name().is_some_and(|name| name == ".gitconfig") => Ok(ConfigFormat::Ini),
+            _ => Err(anyhow!(
+                "Can't tell the format of {}, set format to json, yaml, toml or ini",
+                self.target
+            )),
+        }
+    }
+
+    fn edit_document(&self, document: &mut Value) -> anyhow::Result<()> {
+        if let Some(merge) = &self.merge {
+            merge_value(document, serde_yaml_ng::to_value(merge)?);
+        }
+
+        for (path, value) in self.set.iter() {
+            set_value(document, &key_path(path), serde_yaml_ng::to_value(value)?)
+                .with_context(|| format!("Failed to set {path}"))?;
+        }
+
+        for path in self.delete.iter() {
+            delete_value(document, &key_path(path));
+        }
+
+        Ok(())
+    }
+
+    fn edit_json(&self, contents: &str) -> anyhow::Result<String> {
+        if jsonc(contents) {
+            return Err(anyhow!(
+                "JSON with comments or trailing commas isn't supported, they would be lost"
+            ));
+        }
+
+        let original: Value = match contents.trim().is_empty() {
+            true => Value::Mapping(Mapping::new()),
+            false => serde_json::from_str(contents)?,
+        };
+
+        let mut document = original.clone();
+        self.edit_document(&mut document)?;
+
+        // Unchanged documents are left exactly as they were
+        if document == original {
+            return Ok(contents.to_string());
+        }
+
+        let indent = json_indent(contents);
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut output = vec![];
+        document.serialize(&mut serde_json::Serializer::with_formatter(
+            &mut output,
+            formatter,
+        ))?;
+
+        Ok(format!("{}\n", String::from_utf8(output)?))
+    }
+
+    fn edit_yaml(&self, contents: &str) -> anyhow::Result<String> {
+        let original: Value = match contents.trim().is_empty() {
+            true => Value::Mapping(Mapping::new()),
+            false => serde_yaml_ng::from_str(contents)?,
+        };
+
+        let mut expected = original.clone();
+        self.edit_document(&mut expected)?;
+
+        // Unchanged documents are left exactly as they were
+        if expected == original {
+            return Ok(contents.to_string());
+        }
+
+        let (lines, trailing_newline) = split_lines(contents);
+        let mut yaml = Yaml { lines };
+
+        if let Some(merge) = &self.merge {
+            let serde_json::Value::Object(merge) = merge else {
+                return Err(anyhow!("YAML files can only merge mappings"));
+            };
+
+            yaml.merge(&[], merge)?;
+        }
+
+        for (path, value) in self.set.iter() {
+            yaml.set(&key_path(path), serde_yaml_ng::to_value(value)?)
+                .with_context(|| format!("Failed to set {path}"))?;
+        }
+
+        for path in self.delete.iter() {
+            yaml.delete(&key_path(path));
+        }
+
+        let edited = join_lines(&yaml.lines, trailing_newline);
+
+        // Flow style, anchors and the like aren't edited by line, so make
+        // sure nothing else changed
+        if serde_yaml_ng::from_str::<Value>(&edited)? != expected {
+            return Err(anyhow!(
+                "Can't edit the file by line without changing other settings, which happens with flow style and anchors"
+            ));
+        }
+
+        Ok(edited)
+    }
+
+    fn edit_toml(&self, contents: &str) -> anyhow::Result<String> {
+        let mut document: DocumentMut = contents.parse()?;
+        let table = document.as_table_mut();
+
+        if let Some(merge) = &self.merge {
+            let serde_json::Value::Object(merge) = merge else {
+                return Err(anyhow!("TOML files can only merge tables"));
+            };
+
+            toml_merge(table, merge)?;
+        }
+
+        for (path, value) in self.set.iter() {
+            toml_set(table, &key_path(path), value)
+                .with_context(|| format!("Failed to set {path}"))?;
+        }
+
+        for path in self.delete.iter() {
+            toml_delete(table, &key_path(path));
+        }
+
+        Ok(document.to_string())
+    }
+
+    fn edit_ini(&self, contents: &str) -> anyhow::Result<String> {
+        let (lines, trailing_newline) = split_lines(contents);
+        let mut ini = Ini { lines };
+
+        if let Some(merge) = &self.merge {
+            let serde_json::Value::Object(merge) = merge else {
+                return Err(anyhow!("INI files can only merge sections and keys"));
+            };
+
+            for (name, value) in merge {
+                match value {
+                    serde_json::Value::Object(section) => {
+                        for (key, value) in section {
+                            ini.set(Some(name), key, &ini_value(value)?);
+                        }
+                    }
+                    value => ini.set(None, name, &ini_value(value)?),
+                }
+            }
+        }
+
+        for (path, value) in self.set.iter() {
+            let (section, key) = ini_path(path);
+            ini.set(section.as_deref(), &key, &ini_value(value)?);
+        }
+
+        for path in self.delete.iter() {
+            let (section, key) = ini_path(path);
+            ini.delete(section.as_deref(), &key);
+        }
+
+        Ok(join_lines(&ini.lines, trailing_newline))
+    }
+}
+
+/// Splits a key path on dots, except escaped ones
+fn key_path(path: &str) -> Vec<String> {
+    let mut keys = vec![String::new()];
+    let mut chars = path.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.next_if_eq(&'.').is_some() => keys.last_mut().unwrap().push('.'),
+            '.' => keys.push(String::new()),
+            c => keys.last_mut().unwrap().push(c),
+        }
+    }
+
+    keys
+}
+
+/// Whether JSON has comments or trailing commas, which editors like VS Code
+/// allow in their settings
+fn jsonc(contents: &str) -> bool {
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => (),
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some('/' | '*')) => return true,
+            ',' => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+                if matches!(chars.peek(), Some('}' | ']')) {
+                    return true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    false
+}
+
+/// A TOML item as JSON, to tell whether a setting changes. Datetimes, which
+/// JSON has no type for, are never the same.
+fn toml_json(item: &Item) -> Option<serde_json::Value> {
+    match item {
+        Item::None => None,
+        Item::Value(value) => toml_value_json(value),
+        Item::Table(table) => table
+            .iter()
+            .map(|(key, item)| Some((key.to_string(), toml_json(item)?)))
+            .collect::<Option<_>>()
+            .map(serde_json::Value::Object),
+        Item::ArrayOfTables(tables) => tables
+            .iter()
+            .map(|table| toml_json(&Item::Table(table.clone())))
+            .collect::<Option<_>>()
+            .map(serde_json::Value::Array),
+    }
+}
+
+fn toml_value_json(value: &toml_edit::Value) -> Option<serde_json::Value> {
+    match value {
+        toml_edit::Value::String(value) => Some(value.value().as_str().into()),
+        toml_edit::Value::Integer(value) => Some((*value.value()).into()),
+        toml_edit::Value::Float(value) => Some((*value.value()).into()),
+        toml_edit::Value::Boolean(value) => Some((*value.value()).into()),
+        toml_edit::Value::Datetime(_) => None,
+        toml_edit::Value::Array(values) => values
+            .iter()
+            .map(toml_value_json)
+            .collect::<Option<_>>()
+            .map(serde_json::Value::Array),
+        toml_edit::Value::InlineTable(table) => table
+            .iter()
+            .map(|(key, value)| Some((key.to_string(), toml_value_json(value)?)))
+            .collect::<Option<_>>()
+            .map(serde_json::Value::Object),
+    }
+}
+
+/// JSON objects become tables, other values are kept inline
+fn toml_item(value: &serde_json::Value) -> anyhow::Result<Item> {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut table = toml_edit::Table::new();
+
+            for (key, value) in object {
+                table.insert(key, toml_item(value)?);
+            }
+
+            Ok(Item::Table(table))
+        }
+        value => Ok(Item::Value(toml_value(value)?)),
+    }
+}
+
+fn toml_value(value: &serde_json::Value) -> anyhow::Result<toml_edit::Value> {
+    Ok(match value {
+        serde_json::Value::Null => return Err(anyhow!("TOML has no null values")),
+        serde_json::Value::Bool(value) => (*value).into(),
+        serde_json::Value::Number(value) => match value.as_i64() {
+            Some(value) => value.into(),
+            None => value
+                .as_f64()
+                .ok_or_else(|| anyhow!("{value} is too large for TOML"))?
+                .into(),
+        },
+        serde_json::Value::String(value) => value.into(),
+        serde_json::Value::Array(values) => toml_edit::Value::Array(
+            values
+                .iter()
+                .map(toml_value)
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        serde_json::Value::Object(object) => toml_edit::Value::InlineTable(
+            object
+                .iter()
+                .map(|(key, value)| Ok((key.as_str(), toml_value(value)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+    })
+}
+
+fn toml_merge(
+    table: &mut dyn TableLike,
+    merge: &serde_json::Map<String, serde_json::Value>,
+) -> anyhow::Result<()> {
+    for (key, value) in merge {
+        let child = table.get_mut(key).and_then(Item::as_table_like_mut);
+
+        match (child, value) {
+            (Some(child), serde_json::Value::Object(value)) => toml_merge(child, value)?,
+            _ => toml_set(table, std::slice::from_ref(key), value)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets a value, leaving unchanged ones as they were written
+fn toml_set(
+    table: &mut dyn TableLike,
+    path: &[String],
+    value: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let Some((key, rest)) = path.split_first() else {
+        return Ok(());
+    };
+
+    if rest.is_empty() {
+        if table.get(key).and_then(toml_json).as_ref() != Some(value) {
+            table.insert(key, toml_item(value)?);
+        }
+
+        return Ok(());
+    }
+
+    if !table.contains_key(key) {
+        // Tables only holding other tables don't need a header
+        let mut child = toml_edit::Table::new();
+        child.set_implicit(true);
+        table.insert(key, Item::Table(child));
+    }
+
+    let child = table
+        .get_mut(key)
+        .and_then(Item::as_table_like_mut)
+        .ok_or_else(|| anyhow!("{key} is within a value that isn't a table"))?;
+
+    toml_set(child, rest, value)
+}
+
+/// Removes a value, and the tables it leaves empty. Returns whether `table`
+/// was left empty
+fn toml_delete(table: &mut dyn TableLike, path: &[String]) -> bool {
+    let Some((key, rest)) = path.split_first() else {
+        return false;
+    };
+
+    let removed = match rest.is_empty() {
+        true => table.remove(key).is_some(),
+        false => {
+            table
+                .get_mut(key)
+                .and_then(Item::as_table_like_mut)
+                .is_some_and(|child| toml_delete(child, rest))
+                && table.remove(key).is_some()
+        }
+    };
+
+    removed && table.is_empty()
+}
+
+fn merge_value(document: &mut Value, value: Value) {
+    match (document, value) {
+        (Value::Mapping(document), Value::Mapping(value)) => {
+            for (key, value) in value {
+                match document.get_mut(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        document.insert(key, value);
+                    }
+                }
+            }
+        }
+        (document, value) => *document = value,
+    }
+}
+
+fn set_value(document: &mut Value, path: &[String], value: Value) -> anyhow::Result<()> {
+    let Some((key, rest)) = path.split_first() else {
+        *document = value;
+        return Ok(());
+    };
+
+    let Value::Mapping(mapping) = document else {
+        return Err(anyhow!("{key} is within a value that isn't a table"));
+    };
+
+    let key = Value::String(key.clone());
+
+    if rest.is_empty() {
+        mapping.insert(key, value);
+        return Ok(());
+    }
+
+    let child = mapping
+        .entry(key)
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+
+    set_value(child, rest, value)
+}
+
+/// Removes a value, and the mappings it leaves empty. Returns whether
+/// `document` was left empty
+fn delete_value(document: &mut Value, path: &[String]) -> bool {
+    let Some((key, rest)) = path.split_first() else {
+        return false;
+    };
+
+    let Value::Mapping(mapping) = document else {
+        return false;
+    };
+
+    let removed = match rest.is_empty() {
+        true => mapping.shift_remove(key.as_str()).is_some(),
+        false => {
+            mapping
+                .get_mut(key.as_str())
+                .is_some_and(|child| delete_value(child, rest))
+                && mapping.shift_remove(key.as_str()).is_some()
+        }
+    };
+
+    removed && mapping.is_empty()
+}
+
+/// The indentation of the first indented line, so rewriting keeps it
+fn json_indent(contents: &str) -> String {
+    contents
+        .lines()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+/// The section and key of an INI path: the last key, within the section
+/// named by the rest. Keys without a section come before any section
+fn ini_path(path: &str) -> (Option<String>, String) {
+    let mut keys = key_path(path);
+    let key = keys.pop().unwrap_or_default();
+
+    match keys.is_empty() {
+        true => (None, key),
+        false => (Some(keys.join(".")), key),
+    }
+}
+
+fn ini_value(value: &serde_json::Value) -> anyhow::Result<String> {
+    match value {
+        serde_json::Value::String(value) => Ok(value.clone()),
+        serde_json::Value::Bool(_) | serde_json::Value::Number(_) => Ok(value.to_string()),
+        _ => Err(anyhow!(
+            "INI values can only be strings, numbers or booleans"
+        )),
+    }
+}
+
+/// An INI file edited by line, so comments and formatting are kept
+struct Ini {
+    lines: Vec<String>,
+}
+
+impl Ini {
+    fn section_name(line: &str) -> Option<&str> {
+        line.trim()
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+            .map(str::trim)
+    }
+
+    fn key(line: &str) -> Option<&str> {
+        let line = line.trim();
+
+        if line.starts_with(['#', ';']) {
+            return None;
+        }
+
+        line.split_once('=').map(|(key, _)| key.trim())
+    }
+
+    /// The lines of a section, after its header. Keys without a section are
+    /// the lines before the first header
+    fn section(&self, section: Option<&str>) -> Option<std::ops::Range<usize>> {
+        let start = match section {
+            None => 0,
+            Some(name) => {
+                self.lines
+                    .iter()
+                    .position(|line| Ini::section_name(line) == Some(name))?
+                    + 1
+            }
+        };
+
+        let end = self.lines[start..]
+            .iter()
+            .position(|line| Ini::section_name(line).is_some())
+            .map(|index| start + index)
+            .unwrap_or(self.lines.len());
+
+        Some(start..end)
+    }
+
+    fn set(&mut self, section: Option<&str>, key: &str, value: &str) {
+        let Some(range) = self.section(section) else {
+            if self
+                .lines
+                .last()
+                .is_some_and(|line| !line.trim().is_empty())
+            {
+                self.lines.push(String::new());
+            }
+
+            self.lines
+                .push(format!("[{}]", section.unwrap_or_default()));
+            self.lines.push(format!("{key} = {value}"));
+            return;
+        };
+
+        let keys: Vec<usize> = range
+            .clone()
+            .filter(|index| Ini::key(&self.lines[*index]).is_some())
+            .collect();
+
+        let existing = keys
+            .iter()
+            .find(|index| Ini::key(&self.lines[**index]) == Some(key));
+
+        if let Some(index) = existing {
+            let line = &self.lines[*index];
+            let current = line.split_once('=').map(|(_, value)| value.trim());
+
+            if current != Some(value) {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                self.lines[*index] = format!("{indent}{key} = {value}");
+            }
+
+            return;
+        }
+
+        // New keys go after the last key of the section, indented like it
+        let (index, indent) = match keys.last() {
+            Some(last) => {
+                let line = &self.lines[*last];
+                (
+                    last + 1,
+                    line[..line.len() - line.trim_start().len()].to_string(),
+                )
+            }
+            None => (range.start, String::new()),
+        };
+
+        self.lines.insert(index, format!("{indent}{key} = {value}"));
+    }
+
+    fn delete(&mut self, section: Option<&str>, key: &str) {
+        if let Some(range) = self.section(section) {
+            let removed: Vec<usize> = range
+                .filter(|index| Ini::key(&self.lines[*index]) == Some(key))
+                .collect();
+
+            for index in removed.into_iter().rev() {
+                self.lines.remove(index);
+            }
+        }
+
+        // A path without a section also names a whole section
+        if section.is_none() {
+            if let Some(range) = self.section(Some(key)) {
+                self.lines.drain(range.start - 1..range.end);
+            }
+        }
+    }
+}
+
+/// A key of a YAML block mapping, with the lines of its value
+struct YamlEntry {
+    line: usize,
+    indent: usize,
+    end: usize,
+    /// The key as written, quoted or not
+    key: String,
+    name: String,
+    /// The lines of a nested block mapping
+    child: Option<Range<usize>>,
+    /// Whether it has no value at all
+    empty: bool,
+}
+
+/// A YAML file edited by line, so comments and formatting are kept. Only
+/// block mappings are edited, other values are replaced as a whole
+struct Yaml {
+    lines: Vec<String>,
+}
+
+impl Yaml {
+    fn is_content(line: &str) -> bool {
+        let line = line.trim();
+
+        !line.is_empty() && !line.starts_with('#')
+    }
+
+    fn indent(line: &str) -> usize {
+        line.len() - line.trim_start_matches(' ').len()
+    }
+
+    /// The key as written, the key and the value of a mapping line
+    fn key(line: &str) -> Option<(&str, String, &str)> {
+        let line = line.trim();
+
+        if line.starts_with(['-', '#', '[', '{', '?', '&', '*', '!', '|', '>', '%']) {
+            return None;
+        }
+
+        let end = match line.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let mut escaped = false;
+                let close = line[1..].char_indices().find(|(_, c)| {
+                    let close = *c == quote && !escaped;
+                    escaped = quote == '"' && *c == '\\' && !escaped;
+                    close
+                })?;
+
+                close.0 + 2
+            }
+            _ => {
+                line.char_indices()
+                    .find(|(index, c)| {
+                        *c == ':'
+                            && line[index + 1..]
+                                .chars()
+                                .next()
+                                .is_none_or(char::is_whitespace)
+                    })?
+                    .0
+            }
+        };
+
+        let (written, value) = line.split_at(end);
+        let value = value.strip_prefix(':')?;
+
+        if !value.is_empty() && !value.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        let name = match written.starts_with(['"', '\'']) {
+            true => serde_yaml_ng::from_str(written).ok()?,
+            false => written.to_string(),
+        };
+
+        Some((written, name, value.trim()))
+    }
+
+    /// The keys of the block mapping in `range`
+    fn entries(&self, range: Range<usize>) -> Vec<YamlEntry> {
+        let Some(indent) = self.lines[range.clone()]
+            .iter()
+            .find(|line| Yaml::is_content(line))
+            .map(|line| Yaml::indent(line))
+        else {
+            return vec![];
+        };
+
+        let mut entries = vec![];
+
+        for index in range.clone() {
+            let line = &self.lines[index];
+
+            if !Yaml::is_content(line) || Yaml::indent(line) != indent {
+                continue;
+            }
+
+            let Some((written, name, value)) = Yaml::key(line) else {
+                continue;
+            };
+
+            // The value runs until the next line that isn't indented
+            // further, apart from sequences that aren't indented
+            let mut end = index + 1;
+
+            for (offset, line) in self.lines[index + 1..range.end].iter().enumerate() {
+                if !Yaml::is_content(line) {
+                    continue;
+                }
+
+                let nested = Yaml::indent(line) > indent
+                    || (Yaml::indent(line) == indent && line.trim_start().starts_with('-'));
+
+                if !nested {
+                    break;
+                }
+
+                end = index + offset + 2;
+            }
+
+            let value = value.split(" #").next().unwrap_or_default().trim();
+            let nested = index + 1..end;
+            let child = self.lines[nested.clone()]
+                .iter()
+                .find(|line| Yaml::is_content(line))
+                .filter(|line| value.is_empty() && Yaml::key(line).is_some())
+                .map(|_| nested.clone());
+
+            entries.push(YamlEntry {
+                line: index,
+                indent,
+                end,
+                key: written.to_string(),
+                name,
+                child,
+                empty: (value.is_empty() || value.starts_with('#')) && end == index + 1,
+            });
+        }
+
+        entries
+    }
+
+    /// The entries of as much of `path` as is there
+    fn locate(&self, path: &[String]) -> Vec<YamlEntry> {
+        let mut range = 0..self.lines.len();
+        let mut found = vec![];
+
+        for key in path {
+            let Some(entry) = self
+                .entries(range.clone())
+                .into_iter()
+                .find(|entry| &entry.name == key)
+            else {
+                break;
+            };
+
+            let child = entry.child.clone();
+            found.push(entry);
+
+            match child {
+                Some(child) => range = child,
+                None => break,
+            }
+        }
+
+        found
+    }
+
+    fn render(indent: usize, key: &str, value: &Value) -> anyhow::Result<Vec<String>> {
+        let yaml = serde_yaml_ng::to_string(value)?;
+        let padding = " ".repeat(indent);
+        let nested = match value {
+            Value::Mapping(mapping) => !mapping.is_empty(),
+            Value::Sequence(sequence) => !sequence.is_empty(),
+            _ => false,
+        };
+
+        let mut lines = yaml.lines();
+
+        Ok(match nested {
+            true => std::iter::once(format!("{padding}{key}:"))
+                .chain(lines.map(|line| format!("{padding}  {line}")))
+                .collect(),
+            false => std::iter::once(format!(
+                "{padding}{key}: {}",
+                lines.next().unwrap_or_default()
+            ))
+            .chain(lines.map(|line| format!("{padding}{line}")))
+            .collect(),
+        })
+    }
+
+    /// The value of an entry as it's written
+    fn value(&self, entry: &YamlEntry) -> Option<Value> {
+        let lines: Vec<&str> = self.lines[entry.line..entry.end]
+            .iter()
+            .map(|line| match line.get(..entry.indent) {
+                Some(indent) if indent.trim().is_empty() => &line[entry.indent..],
+                _ => line.trim_start(),
+            })
+            .collect();
+
+        match serde_yaml_ng::from_str(&lines.join("\n")).ok()? {
+            Value::Mapping(mut mapping) => mapping.shift_remove(entry.name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn merge(
+        &mut self,
+        path: &[String],
+        merge: &serde_json::Map<String, serde_json::Value>,
+    ) -> anyhow::Result<()> {
+        for (key, value) in merge {
+            let path: Vec<String> = path.iter().chain(std::iter::once(key)).cloned().collect();
+            let found = self.locate(&path);
+            let mapping = found.len() == path.len() && found.last().unwrap().child.is_some();
+
+            match (mapping, value) {
+                (true, serde_json::Value::Object(value)) => self.merge(&path, value)?,
+                _ => self.set(&path, serde_yaml_ng::to_value(value)?)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, path: &[String], value: Value) -> anyhow::Result<()> {
+        let found = self.locate(path);
+
+        if found.len() == path.len() {
+            let Some(entry) = found.last() else {
+                return Ok(());
+            };
+
+            // Unchanged values are left as they were written
+            if self.value(entry).as_ref() != Some(&value) {
+                let lines = Yaml::render(entry.indent, &entry.key, &value)?;
+                self.lines.splice(entry.line..entry.end, lines);
+            }
+
+            return Ok(());
+        }
+
+        let range = match found.last() {
+            None => 0..self.lines.len(),
+            Some(YamlEntry {
+                child: Some(child), ..
+            }) => child.clone(),
+            Some(entry) => {
+                return Err(anyhow!(
+                    "{} isn't a block mapping, so it can't be edited by line",
+                    entry.name
+                ));
+            }
+        };
+
+        let entries = self.entries(range.clone());
+        let (index, indent) = match (entries.last(), found.last()) {
+            (Some(last), _) => (last.end, last.indent),
+            (None, Some(parent)) => (range.end, parent.indent + 2),
+            (None, None) => (range.end, 0),
+        };
+
+        // Missing mappings are added along with the value
+        let value = path[found.len() + 1..]
+            .iter()
+            .rev()
+            .fold(value, |value, key| {
+                let mut mapping = Mapping::new();
+                mapping.insert(Value::String(key.clone()), value);
+                Value::Mapping(mapping)
+            });
+
+        let key = serde_yaml_ng::to_string(&path[found.len()])?;
+        let lines = Yaml::render(indent, key.trim_end(), &value)?;
+        self.lines.splice(index..index, lines);
+
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &[String]) {
+        let found = self.locate(path);
+
+        let Some(entry) = found.last().filter(|_| found.len() == path.len()) else {
+            return;
+        };
+
+        self.lines.drain(entry.line..entry.end);
+
+        // Mappings left without keys would be read as null, so they go too
+        for depth in (1..path.len()).rev() {
+            let found = self.locate(&path[..depth]);
+
+            match found
+                .last()
+                .filter(|entry| found.len() == depth && entry.empty)
+            {
+                Some(entry) => {
+                    self.lines.drain(entry.line..entry.end);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl ContentEdit for FileConfig {
+    fn apply(&self, contents: &str) -> anyhow::Result<String> {
+        let format = self.format()?;
+
+        match format {
+            ConfigFormat::Ini => return self.edit_ini(contents),
+            ConfigFormat::Json => self.edit_json(contents),
+            ConfigFormat::Yaml => self.edit_yaml(contents),
+            ConfigFormat::Toml => self.edit_toml(contents),
+        }
+        .with_context(|| format!("Failed to edit {} as {:?}", self.target, format))
+    }
+}
+
+impl std::fmt::Display for FileConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys: Vec<&str> = self.set.keys().map(String::as_str).collect();
+
+        write!(f, "its settings")?;
+
+        if self.merge.is_some() {
+            write!(f, " merged")?;
+        }
+
+        if !keys.is_empty() {
+            write!(f, " {} set", keys.join(", "))?;
+        }
+
+        if !self.delete.is_empty() {
+            write!(f, " {} deleted", self.delete.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FileAction for FileConfig {}
+
+impl Action for FileConfig {
+    fn summarize(&self) -> String {
+        format!("Editing settings of {}", self.target)
+    }
+
+    fn plan(&self, _: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
+        // Fail early on invalid options, rather than when applying
+        self.apply("")?;
+
+        Ok(edit_steps(
+            PathBuf::from(&self.target),
+            Box::new(self.clone()),
+            self.create,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Actions;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn config(target: &str) -> FileConfig {
+        FileConfig {
+            target: String::from(target),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_can_be_deserialized() {
+        let yaml = r#"
+- action: file.config
+  target: ~/.config/Code/User/settings.json
+  merge:
+    editor.fontSize: 14
+  set:
+    "[rust].editor\\.formatOnSave": true
+  delete:
+    - telemetry
+"#;
+
+        let mut actions: Vec<Actions> = serde_yaml_ng::from_str(yaml).unwrap();
+
+        match actions.pop() {
+            Some(Actions::FileConfig(action)) => {
+                assert_eq!(Some(json!({"editor.fontSize": 14})), action.action.merge);
+                assert_eq!(
+                    vec!["[rust]", "editor.formatOnSave"],
+                    key_path(action.action.set.keys().next().unwrap())
+                );
+                assert_eq!(ConfigFormat::Json, action.action.format().unwrap());
+            }
+            _ => {
+                panic!("FileConfig didn't deserialize to the correct type");
+            }
+        };
+    }
+
+    #[test]
+    fn it_edits_json() -> anyhow::Result<()> {
+        let mut settings = config("settings.json");
+        settings.merge =
+            Some(json!({"editor.fontSize": 14, "files": {"trimTrailingWhitespace": true}}));
+        settings.delete = vec![String::from("telemetry\\.enable")];
+
+        let contents = "{\n    \"workbench.colorTheme\": \"Nord\",\n    \"telemetry.enable\": true,\n    \"files\": {\n        \"autoSave\": \"off\"\n    }\n}\n";
+
+        let edited = settings.apply(contents)?;
+        assert_eq!(
+            "{\n    \"workbench.colorTheme\": \"Nord\",\n    \"files\": {\n        \"autoSave\": \"off\",\n        \"trimTrailingWhitespace\": true\n    },\n    \"editor.fontSize\": 14\n}\n",
+            edited
+        );
+        assert_eq!(edited, settings.apply(&edited)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_edits_yaml_and_toml() -> anyhow::Result<()> {
+        let mut gh = config("config.yml");
+        gh.set.insert(String::from("git_protocol"), json!("ssh"));
+        gh.set
+            .insert(String::from("aliases.co"), json!("pr checkout"));
+
+        let edited = gh.apply("editor: nvim\ngit_protocol: https\n")?;
+        assert_eq!(
+            "editor: nvim\ngit_protocol: ssh\naliases:\n  co: pr checkout\n",
+            edited
+        );
+        assert_eq!(edited, gh.apply(&edited)?);
+
+        let mut cargo = config("config.toml");
+        cargo
+            .set
+            .insert(String::from("net.git-fetch-with-cli"), json!(true));
+        cargo.delete = vec![String::from("build.jobs")];
+
+        let edited = cargo.apply("[build]\njobs = 4\n")?;
+        assert_eq!("[net]\ngit-fetch-with-cli = true\n", edited);
+        assert_eq!(edited, cargo.apply(&edited)?);
+
+        let edited = cargo.apply("launched = 1997-07-27T07:32:00Z\n\n[build]\njobs = 4\n")?;
+        assert_eq!(
+            "launched = 1997-07-27T07:32:00Z\n\n[net]\ngit-fetch-with-cli = true\n",
+            edited
+        );
+        assert_eq!(edited, cargo.apply(&edited)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keeps_comments_and_formatting() -> anyhow::Result<()> {
+        let mut gh = config("config.yml");
+        gh.set.insert(String::from("git_protocol"), json!("ssh"));
+        gh.set
+            .insert(String::from("aliases.co"), json!("pr checkout"));
+        gh.delete = vec![String::from("prompt.mode")];
+
+        let contents = "# What gh uses to clone\ngit_protocol: https # or ssh\naliases:\n    # Shortcuts\n    pv: \"pr view\"\nprompt:\n  mode: enabled\neditor: nvim\n";
+
+        let edited = gh.apply(contents)?;
+        assert_eq!(
+            "# What gh uses to clone\ngit_protocol: ssh\naliases:\n    # Shortcuts\n    pv: \"pr view\"\n    co: pr checkout\neditor: nvim\n",
+            edited
+        );
+        assert_eq!(edited, gh.apply(&edited)?);
+
+        // Values in flow style can't be edited by line
+        assert!(gh.apply("aliases: {pv: pr view}\n").is_err());
+
+        let mut cargo = config("config.toml");
+        cargo.set.insert(String::from("build.jobs"), json!(8));
+        cargo.merge = Some(json!({"net": {"retry": 3}}));
+
+        let contents = "# Shared by every project\n[build]\njobs = 4 # cores\ntarget-dir = \"/tmp/target\"\n\n[net]\ngit-fetch-with-cli = true\n";
+
+        let edited = cargo.apply(contents)?;
+        assert_eq!(
+            "# Shared by every project\n[build]\njobs = 8\ntarget-dir = \"/tmp/target\"\n\n[net]\ngit-fetch-with-cli = true\nretry = 3\n",
+            edited
+        );
+        assert_eq!(edited, cargo.apply(&edited)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_refuses_json_with_comments() {
+        let mut settings = config("settings.json");
+        settings
+            .set
+            .insert(String::from("editor.fontSize"), json!(14));
+
+        assert!(settings
+            .apply("{\n  // Mine\n  \"editor.tabSize\": 2\n}\n")
+            .is_err());
+        assert!(settings.apply("{\n  \"editor.tabSize\": 2,\n}\n").is_err());
+        assert!(settings
+            .apply("{\n  \"files.exclude\": {\"**/.git\": true},\n  \"url\": \"https://sgc.mil, /*\"\n}\n")
+            .is_ok());
+    }
+
+    #[test]
+    fn it_edits_ini() -> anyhow::Result<()> {
+        let mut git = config(".gitconfig");
+        git.set
+            .insert(String::from("user.name"), json!("Daniel Jackson"));
+        git.set
+            .insert(String::from("user.email"), json!("daniel@sgc.mil"));
+        git.set.insert(String::from("pull.rebase"), json!(true));
+        git.delete = vec![String::from("core.pager")];
+
+        let contents = "# Mine\n[user]\n\tname = Jack\n[core]\n\tpager = less\n";
+
+        let edited = git.apply(contents)?;
+        assert_eq!(
+            "# Mine\n[user]\n\tname = Daniel Jackson\n\temail = daniel@sgc.mil\n[core]\n\n[pull]\nrebase = true\n",
+            edited
+        );
+        assert_eq!(edited, git.apply(&edited)?);
+
+        git.set.clear();
+        git.delete = vec![String::from("core")];
+        assert!(!git.apply(&edited)?.contains("[core]"));
+
+        Ok(())
+    }
+}
@@ -1,19 +1,20 @@
 use super::FileAction;
 use super::{default_chmod, from_octal};
+use crate::actions::Action;
 #[cfg(unix)]
 use crate::atoms::file::Chown;
-use crate::atoms::file::Decrypt;
+use crate::atoms::file::{Decrypt, DecryptionKey};
 use crate::manifests::Manifest;
+use crate::secret_backends::SecretRef;
 use crate::steps::Step;
-use crate::tera_functions::register_functions;
-use crate::{actions::Action, contexts::to_tera};
+use crate::tera_functions::render;
 use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::error::Error as StdError;
+use serde_with::{serde_as, OneOrMany};
 use std::path::PathBuf;
-use tera::Tera;
 
+#[serde_as]
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileCopy {
     #[serde(alias = "source")]
@@ -30,6 +31,15 @@ pub struct FileCopy {
 
     pub passphrase: Option<String>,
 
+    /// Reads the passphrase from a secret backend instead
+    #[serde(default)]
+    pub passphrase_from: Option<SecretRef>,
+
+    /// Age identity files or SSH private keys to decrypt the file with
+    #[serde(default)]
+    #[serde_as(as = "OneOrMany<_>")]
+    pub identity: Vec<String>,
+
     #[serde(rename = "owned_by_user")]
     pub owner_user: Option<String>,
 
@@ -58,28 +68,14 @@ impl Action for FileCopy {
         let contents = match self.load(manifest, &self.from) {
             Ok(contents) => {
                 if self.template {
-                    let mut tera = Tera::default();
-                    register_functions(&mut tera);
-
                     let content_as_str = std::str::from_utf8(&contents)?;
 
-                    match tera.render_str(content_as_str, &to_tera(context)) {
-                        Ok(rendered) => rendered,
-                        Err(err) => match err.source() {
-                            Some(source) => {
-                                return Err(anyhow!(
-                                    "Failed to render contents for FileCopy action: {source}"
-                                ));
-                            }
-                            None => {
-                                return Err(anyhow!(
-                                    "Failed to render contents for FileCopy action: {err}"
-                                ));
-                            }
-                        },
-                    }
-                    .as_bytes()
-                    .to_vec()
+                    render(content_as_str, context)
+                        .map_err(|err| {
+                            anyhow!("Failed to render contents for FileCopy action: {err}")
+                        })?
+                        .as_bytes()
+                        .to_vec()
                 } else {
                     contents
                 }
@@ -131,12 +127,34 @@ impl Action for FileCopy {
 
         #[cfg(unix)]
         let path_for_chown = path.clone();
-        if let Some(passphrase) = self.passphrase.to_owned() {
+        let passphrase = match (&self.passphrase, &self.passphrase_from) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!(
+                    "FileCopy action can't use both passphrase and passphrase_from"
+                ));
+            }
+            (Some(passphrase), None) => Some(passphrase.clone()),
+            (None, Some(secret)) => Some(secret.get()?),
+            (None, None) => None,
+        };
+
+        let key = match (passphrase, self.identity.is_empty()) {
+            (Some(_), false) => {
+                return Err(anyhow!(
+                    "FileCopy action can't use both a passphrase and an identity"
+                ));
+            }
+            (Some(passphrase), true) => Some(DecryptionKey::Passphrase(passphrase)),
+            (None, false) => Some(DecryptionKey::Identities(self.identity.clone())),
+            (None, true) => None,
+        };
+
+        if let Some(key) = key {
             steps.push(Step {
                 atom: Box::new(Decrypt {
                     encrypted_content: contents,
                     path,
-                    passphrase,
+                    key,
                 }),
                 initializers: vec![],
                 finalizers: vec![],
@@ -221,4 +239,88 @@ mod tests {
             }
         };
     }
+
+    #[test]
+    fn it_can_be_deserialized_with_identities() {
+        let yaml = r#"
+- action: file.copy
+  from: a.age
+  to: b
+  identity: ~/.ssh/id_ed25519
+- action: file.copy
+  from: a.age
+  to: b
+  identity:
+    - ~/.config/age/identity.txt
+    - ~/.ssh/id_ed25519
+"#;
+
+        let actions: Vec<Actions> = serde_yaml_ng::from_str(yaml).unwrap();
+
+        let identities: Vec<Vec<String>> = actions
+            .into_iter()
+            .map(|action| match action {
+                Actions::FileCopy(action) => action.action.identity,
+                _ => panic!("FileCopy didn't deserialize to the correct type"),
+            })
+            .collect();
+
+        assert_eq!(vec!["~/.ssh/id_ed25519"], identities[0]);
+        assert_eq!(
+            vec!["~/.config/age/identity.txt", "~/.ssh/id_ed25519"],
+            identities[1]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_reads_the_passphrase_from_a_secret_backend() -> anyhow::Result<()> {
+        use crate::actions::file::copy::FileCopy;
+        use crate::actions::Action;
+        use crate::manifests::Manifest;
+        use crate::secret_backends::{register_backend, tests::stub, SecretRef};
+        use age::secrecy::SecretString;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("files"))?;
+
+        let mut encrypted = vec![];
+        let mut writer = age::Encryptor::with_user_passphrase(SecretString::from("Teal'c"))
+            .wrap_output(&mut encrypted)?;
+        writer.write_all(b"Shol'va")?;
+        writer.finish()?;
+        std::fs::write(dir.path().join("files").join("secret.age"), encrypted)?;
+
+        let pass = stub(dir.path(), "pass", "echo \"Teal'c\"");
+        register_backend(crate::secret_backends::tests::Pass {
+            name: String::from("copy-pass-stub"),
+            program: pass.display().to_string(),
+        });
+
+        let to = dir.path().join("secret");
+        let action = FileCopy {
+            from: String::from("secret.age"),
+            to: to.display().to_string(),
+            chmod: 0o600,
+            passphrase_from: Some(SecretRef {
+                backend: String::from("copy-pass-stub"),
+                path: String::from("comtrya/files"),
+            }),
+            ..Default::default()
+        };
+
+        let manifest = Manifest {
+            root_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        for mut step in action.plan(&manifest, &Default::default())? {
+            step.atom.execute()?;
+        }
+
+        assert_eq!("Shol'va", std::fs::read_to_string(to)?);
+
+        Ok(())
+    }
 }
@@ -2,12 +2,16 @@ use super::FileAction;
 use super::{default_chmod, from_octal};
 #[cfg(unix)]
 use crate::atoms::file::Chown;
+use crate::atoms::http::Checksum;
+use crate::http::{Auth, Request, RequestOptions, Value};
 use crate::manifests::Manifest;
 use crate::steps::Step;
 use crate::{actions::Action, contexts::Contexts};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename = "file.download")]
@@ -26,13 +30,58 @@ pub struct FileDownload {
 
     #[serde(rename = "owned_by_group")]
     pub owner_group: Option<String>,
+
+    /// The SHA-256 the download has to match
+    pub sha256: Option<String>,
+
+    /// The SHA-512 the download has to match
+    pub sha512: Option<String>,
+
+    /// URL of a checksum file to verify the download with
+    pub checksum_url: Option<String>,
+
+    /// Seconds after which the file is downloaded again
+    pub ttl: Option<u64>,
+
+    /// Download again when the ETag or Last-Modified of `from` changed
+    #[serde(default)]
+    pub revalidate: bool,
+
+    /// Sent with the requests, in addition to those of `Comtrya.yaml`
+    #[serde(default)]
+    pub headers: BTreeMap<String, Value>,
+
+    #[serde(default)]
+    pub auth: Option<Auth>,
+
+    /// Seconds the download may take, instead of the configured timeout
+    pub timeout: Option<u64>,
+
+    /// Times a failed download is retried, instead of the configured retries
+    pub retries: Option<u32>,
 }
 
 fn default_template() -> bool {
     false
 }
 
-impl FileDownload {}
+impl FileDownload {
+    fn checksum(&self) -> anyhow::Result<Option<Checksum>> {
+        let checksum = match (&self.sha256, &self.sha512, &self.checksum_url) {
+            (None, None, None) => None,
+            (Some(sha256), None, None) => Some(Checksum::Sha256(sha256.clone())),
+            (None, Some(sha512), None) => Some(Checksum::Sha512(sha512.clone())),
+            (None, None, Some(url)) => Some(Checksum::Url(url.clone())),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Only one of sha256, sha512 and checksum_url can be set"
+                ))
+            }
+        };
+
+        Ok(checksum)
+    }
+}
 
 impl FileAction for FileDownload {}
 
@@ -41,7 +90,7 @@ impl Action for FileDownload {
         format!("Downloading file {} to {}", self.from, self.to)
     }
 
-    fn plan(&self, _manifest: &Manifest, _context: &Contexts) -> anyhow::Result<Vec<Step>> {
+    fn plan(&self, _manifest: &Manifest, context: &Contexts) -> anyhow::Result<Vec<Step>> {
         use crate::atoms::directory::Create as DirCreate;
         use crate::atoms::file::Chmod;
         use crate::atoms::http::Download;
@@ -67,8 +116,19 @@ impl Action for FileDownload {
             },
             Step {
                 atom: Box::new(Download {
-                    url: self.from.clone(),
-                    to: path.clone(),
+                    template: self.template.then(|| context.clone()),
+                    checksum: self.checksum()?,
+                    ttl: self.ttl.map(Duration::from_secs),
+                    revalidate: self.revalidate,
+                    request: Request {
+                        options: RequestOptions {
+                            headers: self.headers.clone(),
+                            auth: self.auth.clone(),
+                        },
+                        timeout: self.timeout.map(Duration::from_secs),
+                        retries: self.retries,
+                    },
+                    ..Download::new(self.from.clone(), path.clone())
                 }),
                 initializers: vec![],
                 finalizers: vec![],
@@ -175,6 +235,7 @@ mod tests {
             template: false,
             owner_user: Some("test".to_string()),
             owner_group: Some("test".to_string()),
+            ..Default::default()
         };
 
         let steps = file_download.plan(&Default::default(), &Default::default());
@@ -182,4 +243,38 @@ mod tests {
         let steps = steps.unwrap();
         assert_eq!(4, steps.len());
     }
+
+    #[test]
+    fn it_takes_one_checksum() {
+        use crate::actions::file::download::FileDownload;
+        use crate::actions::Action;
+
+        let yaml = r#"
+- action: file.download
+  from: https://example.com/tool.tar.gz
+  to: /tmp/tool.tar.gz
+  checksum_url: https://example.com/SHA256SUMS
+  ttl: 86400
+  revalidate: true
+"#;
+
+        let mut actions: Vec<Actions> = serde_yaml_ng::from_str(yaml).unwrap();
+
+        let Some(Actions::FileDownload(action)) = actions.pop() else {
+            panic!("FileDownload didn't deserialize to the correct type");
+        };
+
+        assert_eq!(Some(86400), action.action.ttl);
+        assert!(action.action.revalidate);
+        assert!(action
+            .action
+            .plan(&Default::default(), &Default::default())
+            .is_ok());
+
+        let both = FileDownload {
+            sha256: Some("a".repeat(64)),
+            ..action.action
+        };
+        assert!(both.plan(&Default::default(), &Default::default()).is_err());
+    }
 }
@@ -0,0 +1,264 @@
+use super::FileAction;
+use crate::actions::Action;
+use crate::atoms::file::{ContentEdit, EditContents};
+use crate::contexts::Contexts;
+use crate::manifests::Manifest;
+use crate::steps::Step;
+use anyhow::anyhow;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Whether managed contents should be in the file
+#[derive(JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    #[default]
+    Present,
+    Absent,
+}
+
+#[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileLine {
+    pub target: String,
+
+    pub line: Option<String>,
+
+    #[serde(default)]
+    pub state: State,
+
+    /// The last line matching this is replaced by `line`. When absent, every
+    /// matching line is removed
+    pub regex: Option<String>,
+
+    /// Where a missing line goes, after the last line matching this
+    pub insert_after: Option<String>,
+
+    /// Where a missing line goes, before the first line matching this
+    pub insert_before: Option<String>,
+
+    /// Create the file when it doesn't exist
+    #[serde(default)]
+    pub create: bool,
+}
+
+/// Splits contents into lines, and whether they end with a newline
+pub(crate) fn split_lines(contents: &str) -> (Vec<String>, bool) {
+    match contents.strip_suffix('\n') {
+        Some(body) => (body.split('\n').map(String::from).collect(), true),
+        None if contents.is_empty() => (vec![], true),
+        None => (contents.split('\n').map(String::from).collect(), false),
+    }
+}
+
+pub(crate) fn join_lines(lines: &[String], trailing_newline: bool) -> String {
+    match trailing_newline && !lines.is_empty() {
+        true => format!("{}\n", lines.join("\n")),
+        false => lines.join("\n"),
+    }
+}
+
+/// Compares a line without the carriage return of Windows line endings
+pub(crate) fn same_line(line: &str, expected: &str) -> bool {
+    line.trim_end_matches('\r') == expected
+}
+
+fn regex(pattern: &Option<String>) -> anyhow::Result<Option<Regex>> {
+    pattern
+        .as_deref()
+        .map(|pattern| Regex::new(pattern).map_err(|err| anyhow!("Invalid regex {pattern}: {err}")))
+        .transpose()
+}
+
+/// Where to insert missing lines: after the last line matching `after`,
+/// before the first line matching `before`, or at the end
+pub(crate) fn insert_position(
+    lines: &[String],
+    after: &Option<String>,
+    before: &Option<String>,
+) -> anyhow::Result<usize> {
+    let position = match (regex(after)?, regex(before)?) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "insert_after and insert_before can't be used together"
+            ))
+        }
+        (Some(after), None) => lines
+            .iter()
+            .rposition(|line| after.is_match(line))
+            .map(|index| index + 1),
+        (None, Some(before)) => lines.iter().position(|line| before.is_match(line)),
+        (None, None) => None,
+    };
+
+    Ok(position.unwrap_or(lines.len()))
+}
+
+impl ContentEdit for FileLine {
+    fn apply(&self, contents: &str) -> anyhow::Result<String> {
+        let (mut lines, trailing_newline) = split_lines(contents);
+        let regex = regex(&self.regex)?;
+
+        match (self.state, &self.line) {
+            (State::Present, None) => return Err(anyhow!("file.line needs a line to add")),
+            (State::Present, Some(line)) => {
+                let matched = regex
+                    .and_then(|regex| lines.iter().rposition(|existing| regex.is_match(existing)));
+
+                if let Some(index) = matched {
+                    lines[index] = line.clone();
+                } else if !lines.iter().any(|existing| same_line(existing, line)) {
+                    let index = insert_position(&lines, &self.insert_after, &self.insert_before)?;
+                    lines.insert(index, line.clone());
+                }
+            }
+            (State::Absent, line) => match (regex, line) {
+                (Some(regex), _) => lines.retain(|existing| !regex.is_match(existing)),
+                (None, Some(line)) => lines.retain(|existing| !same_line(existing, line)),
+                (None, None) => return Err(anyhow!("file.line needs a line or regex to remove")),
+            },
+        }
+
+        Ok(join_lines(&lines, trailing_newline))
+    }
+}
+
+impl std::fmt::Display for FileLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.state, &self.line, &self.regex) {
+            (State::Present, line, _) => {
+                write!(f, "the line {:?}", line.as_deref().unwrap_or_default())
+            }
+            (State::Absent, _, Some(regex)) => write!(f, "lines matching {regex:?} removed"),
+            (State::Absent, line, None) => write!(
+                f,
+                "the line {:?} removed",
+                line.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+impl FileAction for FileLine {}
+
+impl Action for FileLine {
+    fn summarize(&self) -> String {
+        format!("Editing a line of {}", self.target)
+    }
+
+    fn plan(&self, _: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
+        // Fail early on invalid options, rather than when applying
+        self.apply("")?;
+
+        Ok(edit_steps(
+            PathBuf::from(&self.target),
+            Box::new(self.clone()),
+            self.create,
+        ))
+    }
+}
+
+/// Edits `path`, creating its directory first when the file is created
+pub(crate) fn edit_steps(path: PathBuf, edit: Box<dyn ContentEdit>, create: bool) -> Vec<Step> {
+    use crate::atoms::directory::Create as DirCreate;
+
+    let mut steps = vec![];
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| create && *parent != PathBuf::new())
+    {
+        steps.push(Step {
+            atom: Box::new(DirCreate {
+                path: parent.to_path_buf(),
+            }),
+            initializers: vec![],
+            finalizers: vec![],
+        });
+    }
+
+    steps.push(Step {
+        atom: Box::new(EditContents { path, edit, create }),
+        initializers: vec![],
+        finalizers: vec![],
+    });
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Actions;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_be_deserialized() {
+        let yaml = r#"
+- action: file.line
+  target: /etc/ssh/sshd_config
+  line: PasswordAuthentication no
+  regex: ^#?PasswordAuthentication
+"#;
+
+        let mut actions: Vec<Actions> = serde_yaml_ng::from_str(yaml).unwrap();
+
+        match actions.pop() {
+            Some(Actions::FileLine(action)) => {
+                assert_eq!("/etc/ssh/sshd_config", action.action.target);
+                assert_eq!(State::Present, action.action.state);
+                assert_eq!(
+                    Some("^#?PasswordAuthentication"),
+                    action.action.regex.as_deref()
+                );
+            }
+            _ => {
+                panic!("FileLine didn't deserialize to the correct type");
+            }
+        };
+    }
+
+    #[test]
+    fn it_edits_lines() -> anyhow::Result<()> {
+        let config = "Port 22\n#PasswordAuthentication yes\nUsePAM yes\n";
+
+        let replace = FileLine {
+            line: Some(String::from("PasswordAuthentication no")),
+            regex: Some(String::from("^#?PasswordAuthentication")),
+            ..Default::default()
+        };
+        let replaced = replace.apply(config)?;
+        assert_eq!("Port 22\nPasswordAuthentication no\nUsePAM yes\n", replaced);
+        assert_eq!(replaced, replace.apply(&replaced)?);
+
+        let insert = FileLine {
+            line: Some(String::from("ListenAddress 0.0.0.0")),
+            insert_after: Some(String::from("^Port")),
+            ..Default::default()
+        };
+        assert_eq!(
+            "Port 22\nListenAddress 0.0.0.0\n#PasswordAuthentication yes\nUsePAM yes\n",
+            insert.apply(config)?
+        );
+
+        let append = FileLine {
+            line: Some(String::from("127.0.0.1 abydos")),
+            ..Default::default()
+        };
+        assert_eq!("127.0.0.1 abydos\n", append.apply("")?);
+        assert_eq!(
+            "::1 localhost\n127.0.0.1 abydos",
+            append.apply("::1 localhost")?
+        );
+
+        let remove = FileLine {
+            regex: Some(String::from("PasswordAuthentication")),
+            state: State::Absent,
+            ..Default::default()
+        };
+        assert_eq!("Port 22\nUsePAM yes\n", remove.apply(config)?);
+
+        Ok(())
+    }
+}
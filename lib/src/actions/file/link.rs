@@ -1,12 +1,18 @@
 use super::FileAction;
+use crate::atoms::file::OnConflict;
 use crate::manifests::Manifest;
 use crate::steps::initializers::FileExists;
-use crate::steps::initializers::FlowControl::Ensure;
+use crate::steps::initializers::FlowControl::{self, Ensure};
 use crate::steps::Step;
-use crate::{actions::Action, contexts::Contexts};
+use crate::{
+    actions::{glob_set, Action},
+    contexts::Contexts,
+};
+use ignore::WalkBuilder;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::vec;
 use tracing::error;
 
@@ -21,6 +27,26 @@ pub struct FileLink {
 
     #[serde(default = "walk_dir_default")]
     pub walk_dir: bool,
+
+    /// Walk the whole tree of `source`, linking files in mirrored directories
+    #[serde(default)]
+    pub recursive: bool,
+
+    /// When walking, only link files matching one of these globs
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// When walking, leave out files and directories matching these globs
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// When walking, remove links into `source` whose file was removed
+    #[serde(default)]
+    pub remove_stale: bool,
+
+    /// What to do when the target exists and isn't a link
+    #[serde(default)]
+    pub on_conflict: OnConflict,
 }
 
 fn walk_dir_default() -> bool {
@@ -53,7 +79,15 @@ impl FileLink {
         }
     }
 
-    pub fn plan_no_walk(from: PathBuf, to: PathBuf) -> Vec<Step> {
+    /// Adopted sources don't need to exist yet
+    fn initializers(&self, from: &Path) -> Vec<FlowControl> {
+        match self.on_conflict {
+            OnConflict::Adopt => vec![],
+            _ => vec![Ensure(Box::new(FileExists(from.to_path_buf())))],
+        }
+    }
+
+    pub fn plan_no_walk(&self, from: PathBuf, to: PathBuf) -> Vec<Step> {
         use crate::atoms::directory::Create as DirCreate;
         use crate::atoms::file::Link;
 
@@ -68,11 +102,8 @@ impl FileLink {
                         finalizers: vec![],
                     },
                     Step {
-                        atom: Box::new(Link {
-                            source: from.to_owned(),
-                            target: to,
-                        }),
-                        initializers: vec![Ensure(Box::new(FileExists(from)))],
+                        atom: Box::new(Link::new(from.to_owned(), to, self.on_conflict)),
+                        initializers: self.initializers(&from),
                         finalizers: vec![],
                     },
                 ]
@@ -81,9 +112,14 @@ impl FileLink {
         }
     }
 
-    pub fn plan_walk(from: PathBuf, to: PathBuf) -> Vec<Step> {
+    /// Links the entries of `from`. Recursively, directories are created
+    /// instead of linked, so only files are links, like GNU stow
+    pub fn plan_walk(&self, from: PathBuf, to: PathBuf) -> anyhow::Result<Vec<Step>> {
         use crate::atoms::directory::Create as DirCreate;
-        use crate::atoms::file::Link;
+        use crate::atoms::file::{Link, Remove};
+
+        let include = glob_set(&self.include, "file.link")?;
+        let exclude = glob_set(&self.exclude, "file.link")?;
 
         let mut steps = vec![Step {
             atom: Box::new(DirCreate { path: to.clone() }),
@@ -91,53 +127,148 @@ impl FileLink {
             finalizers: vec![],
         }];
 
-        if let Ok(paths) = std::fs::read_dir(from) {
-            paths.for_each(|path| {
-                if let Ok(path) = path {
-                    let p = path.path();
-
-                    if let Some(file_name) = p.file_name() {
-                        steps.push(Step {
-                            atom: Box::new(Link {
-                                source: p.clone(),
-                                target: to.join(file_name),
-                            }),
-                            initializers: vec![Ensure(Box::new(FileExists(p.clone())))],
-                            finalizers: vec![],
-                        })
-                    }
-                }
+        let mut directories = vec![to.clone()];
+        let mut created = BTreeSet::from([to.clone()]);
+
+        let root = from.clone();
+        let walker = WalkBuilder::new(&from)
+            .standard_filters(false)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .follow_links(false)
+            .max_depth(match self.recursive {
+                true => None,
+                false => Some(1),
             })
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                entry
+                    .path()
+                    .strip_prefix(&root)
+                    .map(|relative| !exclude.is_match(relative))
+                    .unwrap_or(true)
+            })
+            .build();
+
+        for entry in walker {
+            let entry = entry?;
+            let path = entry.path();
+
+            let relative = path.strip_prefix(&from)?;
+            if relative.as_os_str().is_empty() || relative.as_os_str() == IGNORE_FILE {
+                continue;
+            }
+
+            let target = to.join(relative);
+            let is_dir = entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir());
+
+            if is_dir && self.recursive {
+                directories.push(target);
+                continue;
+            }
+
+            if !include.is_empty() && !include.is_match(relative) {
+                continue;
+            }
+
+            if let Some(parent) = target
+                .parent()
+                .filter(|parent| created.insert(parent.to_path_buf()))
+            {
+                steps.push(Step {
+                    atom: Box::new(DirCreate {
+                        path: parent.to_path_buf(),
+                    }),
+                    initializers: vec![],
+                    finalizers: vec![],
+                });
+            }
+
+            steps.push(Step {
+                atom: Box::new(Link::new(path.to_path_buf(), target, self.on_conflict)),
+                initializers: self.initializers(path),
+                finalizers: vec![],
+            });
+        }
+
+        if self.remove_stale {
+            for link in directories
+                .iter()
+                .flat_map(|directory| stale_links(&from, directory))
+            {
+                steps.push(Step {
+                    atom: Box::new(Remove { target: link }),
+                    initializers: vec![],
+                    finalizers: vec![],
+                });
+            }
         }
 
-        steps
+        Ok(steps)
     }
 }
 
+/// Lists patterns to leave out when walking, one glob per line like .gitignore
+const IGNORE_FILE: &str = ".comtryaignore";
+
+/// Links in `directory` that point into `source`, to files that no longer exist
+fn stale_links(source: &Path, directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            std::fs::read_link(path).is_ok_and(|link| {
+                let link = directory.join(link);
+                link.starts_with(source) && std::fs::symlink_metadata(&link).is_err()
+            })
+        })
+        .collect()
+}
+
 impl FileAction for FileLink {}
 
 impl Action for FileLink {
     fn summarize(&self) -> String {
-        format!(
+        let summary = format!(
             "Linking file {} to {}",
             self.from.clone().unwrap_or(String::from("unknown")),
             self.to.clone().unwrap_or(String::from("unknown"))
-        )
+        );
+
+        match self.on_conflict {
+            OnConflict::Skip => summary,
+            on_conflict => format!("{summary}, existing files are {on_conflict}"),
+        }
     }
 
     fn plan(&self, manifest: &Manifest, _: &Contexts) -> anyhow::Result<Vec<Step>> {
-        let from: PathBuf = self.resolve(manifest, self.source().as_str())?;
+        let from: PathBuf = match self.resolve(manifest, self.source().as_str()) {
+            Ok(from) => from,
+            // Adopting creates the source
+            Err(_) if self.on_conflict == OnConflict::Adopt => manifest
+                .root_dir
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("Failed because manifest has no root_dir"))?
+                .join("files")
+                .join(self.source()),
+            Err(err) => return Err(err),
+        };
 
         let to = PathBuf::from(self.target());
 
         // Can't walk a file
         if from.is_file() {
-            return Ok(FileLink::plan_no_walk(from, to));
+            return Ok(self.plan_no_walk(from, to));
         }
 
         match self.walk_dir {
-            false => Ok(FileLink::plan_no_walk(from, to)),
-            true => Ok(FileLink::plan_walk(from, to)),
+            false => Ok(self.plan_no_walk(from, to)),
+            true => self.plan_walk(from, to),
         }
     }
 }
@@ -152,6 +283,7 @@ mod tests {
     };
 
     use super::FileLink;
+    use crate::atoms::file::OnConflict;
 
     #[test]
     fn it_can_be_deserialized() {
@@ -173,6 +305,24 @@ mod tests {
             }
         };
 
+        let yaml = r#"
+- action: file.link
+  source: a
+  target: b
+  on_conflict: adopt
+"#;
+
+        let mut actions: Vec<Actions> = serde_yaml_ng::from_str(yaml).unwrap();
+
+        match actions.pop() {
+            Some(Actions::FileLink(action)) => {
+                assert_eq!(OnConflict::Adopt, action.action.on_conflict);
+            }
+            _ => {
+                panic!("FileLink didn't deserialize to the correct type");
+            }
+        };
+
         // Old style format
         let yaml = r#"
 - action: file.link
@@ -284,4 +434,76 @@ mod tests {
         let steps = file_link_action.plan(&manifest, &contexts).unwrap();
         assert_eq!(steps.len(), number_of_files + 1);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_can_walk_link_directories_recursively() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let files = root.path().join("files").join("dotfiles");
+        let home = root.path().join("home");
+
+        std::fs::create_dir_all(files.join(".config/nvim/after"))?;
+        std::fs::create_dir_all(files.join(".cache"))?;
+        std::fs::write(files.join(".bashrc"), "")?;
+        std::fs::write(files.join("README.md"), "")?;
+        std::fs::write(files.join(".config/nvim/init.lua"), "")?;
+        std::fs::write(files.join(".config/nvim/after/ftplugin.lua"), "")?;
+        std::fs::write(files.join(".cache/history"), "")?;
+        std::fs::write(files.join(".comtryaignore"), "# Not config\n.cache\n")?;
+
+        // Left behind by a file removed from the dotfiles
+        std::fs::create_dir_all(home.join(".config/nvim"))?;
+        std::os::unix::fs::symlink(
+            files.canonicalize()?.join(".config/nvim/old.lua"),
+            home.join(".config/nvim/old.lua"),
+        )?;
+
+        let manifest = Manifest {
+            root_dir: Some(root.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let file_link_action = FileLink {
+            source: Some(String::from("dotfiles")),
+            target: Some(home.display().to_string()),
+            walk_dir: true,
+            recursive: true,
+            exclude: vec![String::from("*.md")],
+            remove_stale: true,
+            ..Default::default()
+        };
+
+        let contexts = build_contexts(&Config::default());
+
+        for mut step in file_link_action.plan(&manifest, &contexts)? {
+            if step.do_initializers_allow_us_to_run() && step.atom.plan()?.should_run {
+                step.atom.execute()?;
+            }
+        }
+
+        assert!(home.join(".bashrc").is_symlink());
+        assert!(home.join(".config/nvim/init.lua").is_symlink());
+        assert!(home.join(".config/nvim/after/ftplugin.lua").is_symlink());
+        assert!(!home.join(".config").is_symlink());
+        assert!(!home.join(".config/nvim/old.lua").is_symlink());
+        assert!(!home.join("README.md").exists());
+        assert!(!home.join(".cache").exists());
+        assert!(!home.join(".comtryaignore").exists());
+
+        // Including narrows it down to matching files
+        let file_link_action = FileLink {
+            include: vec![String::from("**/*.lua")],
+            remove_stale: false,
+            ..file_link_action
+        };
+
+        let links = file_link_action
+            .plan(&manifest, &contexts)?
+            .iter()
+            .filter(|step| step.atom.to_string().contains("linked"))
+            .count();
+        assert_eq!(2, links);
+
+        Ok(())
+    }
 }
@@ -1,6 +1,9 @@
+pub mod block;
 pub mod chown;
+pub mod config;
 pub mod copy;
 pub mod download;
+pub mod line;
 pub mod link;
 pub mod remove;
 pub mod unarchive;
@@ -63,6 +66,15 @@ where
     u32::from_str_radix(&chmod, 8).map_err(D::Error::custom)
 }
 
+pub(crate) fn from_octal_option<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|chmod| u32::from_str_radix(&chmod, 8).map_err(D::Error::custom))
+        .transpose()
+}
+
 fn default_chmod() -> u32 {
     0o644
 }
@@ -1,5 +1,5 @@
 use super::FileAction;
-use crate::atoms::file::Unarchive;
+use crate::atoms::file::{MemberFilter, Unarchive};
 use crate::manifests::Manifest;
 use crate::steps::Step;
 use crate::{actions::Action, contexts::Contexts};
@@ -15,9 +15,26 @@ pub struct FileUnarchive {
     pub to: String,
 
     pub force: Option<bool>,
+
+    /// Leading path components removed from every member, like tar's
+    /// --strip-components
+    #[serde(default)]
+    pub strip_components: usize,
+
+    /// When set, only members matching one of these globs are extracted
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Members matching these globs aren't extracted
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
-impl FileUnarchive {}
+impl FileUnarchive {
+    fn filter(&self) -> anyhow::Result<MemberFilter> {
+        MemberFilter::new(self.strip_components, &self.include, &self.exclude)
+    }
+}
 
 impl FileAction for FileUnarchive {}
 
@@ -31,7 +48,8 @@ impl Action for FileUnarchive {
             atom: Box::new(Unarchive {
                 origin: self.from.clone().into(),
                 dest: self.to.clone().into(),
-                force: self.force.unwrap_or(true),
+                force: self.force,
+                filter: self.filter()?,
             }),
             initializers: vec![],
             finalizers: vec![],
@@ -59,6 +77,7 @@ mod tests {
             Some(Actions::FileUnarchive(action)) => {
                 assert_eq!("a", action.action.from);
                 assert_eq!("b", action.action.to);
+                assert_eq!(0, action.action.strip_components);
             }
             _ => {
                 panic!("FileCopy didn't deserialize to the correct type");
@@ -15,13 +15,17 @@ use anyhow::anyhow;
 use binary::BinaryGitHub;
 use command::run::RunCommand;
 use directory::{DirectoryCopy, DirectoryCreate, DirectoryRemove};
+use file::block::FileBlock;
 use file::chown::FileChown;
+use file::config::FileConfig;
 use file::copy::FileCopy;
 use file::download::FileDownload;
+use file::line::FileLine;
 use file::link::FileLink;
 use file::remove::FileRemove;
 use file::unarchive::FileUnarchive;
 use git::GitClone;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use group::add::GroupAdd;
 use package::{PackageInstall, PackageRepository};
 use plugin::Plugin;
@@ -121,6 +125,12 @@ pub enum Actions {
     #[serde(rename = "directory.create", alias = "dir.create")]
     DirectoryCreate(ConditionalVariantAction<DirectoryCreate>),
 
+    #[serde(rename = "file.block")]
+    FileBlock(ConditionalVariantAction<FileBlock>),
+
+    #[serde(rename = "file.config")]
+    FileConfig(ConditionalVariantAction<FileConfig>),
+
     #[serde(rename = "file.copy")]
     FileCopy(ConditionalVariantAction<FileCopy>),
 
@@ -130,6 +140,9 @@ pub enum Actions {
     #[serde(rename = "file.download")]
     FileDownload(ConditionalVariantAction<FileDownload>),
 
+    #[serde(rename = "file.line")]
+    FileLine(ConditionalVariantAction<FileLine>),
+
     #[serde(rename = "file.link")]
     FileLink(ConditionalVariantAction<FileLink>),
 
@@ -182,9 +195,12 @@ impl Actions {
             Actions::CommandRun(a) => a,
             Actions::DirectoryCopy(a) => a,
             Actions::DirectoryCreate(a) => a,
+            Actions::FileBlock(a) => a,
+            Actions::FileConfig(a) => a,
             Actions::FileCopy(a) => a,
             Actions::FileChown(a) => a,
             Actions::FileDownload(a) => a,
+            Actions::FileLine(a) => a,
             Actions::FileLink(a) => a,
             Actions::FileUnarchive(a) => a,
             Actions::GitClone(a) => a,
@@ -209,9 +225,12 @@ impl Deref for Actions {
             Actions::CommandRun(a) => a,
             Actions::DirectoryCopy(a) => a,
             Actions::DirectoryCreate(a) => a,
+            Actions::FileBlock(a) => a,
+            Actions::FileConfig(a) => a,
             Actions::FileCopy(a) => a,
             Actions::FileChown(a) => a,
             Actions::FileDownload(a) => a,
+            Actions::FileLine(a) => a,
             Actions::FileLink(a) => a,
             Actions::FileUnarchive(a) => a,
             Actions::GitClone(a) => a,
@@ -234,9 +253,12 @@ impl Display for Actions {
             Actions::CommandRun(_) => "command.run",
             Actions::DirectoryCopy(_) => "directory.copy",
             Actions::DirectoryCreate(_) => "directory.create",
+            Actions::FileBlock(_) => "file.block",
+            Actions::FileConfig(_) => "file.config",
             Actions::FileCopy(_) => "file.copy",
             Actions::FileChown(_) => "file.chown",
             Actions::FileDownload(_) => "file.download",
+            Actions::FileLine(_) => "file.line",
             Actions::FileLink(_) => "file.link",
             Actions::FileRemove(_) => "file.remove",
             Actions::FileUnarchive(_) => "file.unarchive",
@@ -276,6 +298,20 @@ impl<E: std::error::Error> From<E> for ActionError {
     }
 }
 
+/// Compiles the include or exclude `patterns` of `action`
+pub(crate) fn glob_set(patterns: &[String], action: &str) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        builder.add(
+            Glob::new(pattern)
+                .map_err(|err| anyhow!("Invalid pattern '{}' for {}: {}", pattern, action, err))?,
+        );
+    }
+
+    Ok(builder.build()?)
+}
+
 pub trait Action {
     fn summarize(&self) -> String {
         warn!("need to define action summarize");
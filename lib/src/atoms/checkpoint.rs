@@ -0,0 +1,213 @@
+use crate::utilities::{remove, symlink};
+use anyhow::{Context, Result};
+use std::fs::Permissions;
+use std::path::{Path, PathBuf};
+
+/// The state of paths before an atom changed them, so atomic manifests can
+/// put them back when a later step fails
+#[derive(Debug)]
+pub struct Checkpoint {
+    snapshots: Vec<(PathBuf, State)>,
+}
+
+#[derive(Debug)]
+enum State {
+    Missing,
+    File {
+        contents: Vec<u8>,
+        permissions: Permissions,
+        owner: Option<(u32, u32)>,
+    },
+    Symlink {
+        target: PathBuf,
+    },
+    Directory {
+        permissions: Permissions,
+        owner: Option<(u32, u32)>,
+    },
+}
+
+impl Checkpoint {
+    /// Captures `paths`. Directory contents aren't captured, only the
+    /// directory itself.
+    pub fn of<P: AsRef<Path>>(paths: &[P]) -> Result<Checkpoint> {
+        let snapshots = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let state = State::take(path)
+                    .with_context(|| format!("Failed to capture {}", path.display()))?;
+
+                Ok((path.to_path_buf(), state))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Checkpoint { snapshots })
+    }
+
+    /// Captures the directory `path` would be created from, the top-most of
+    /// it and its parents that doesn't exist yet
+    pub fn of_created_dir(path: &Path) -> Result<Checkpoint> {
+        let created = path
+            .ancestors()
+            .take_while(|ancestor| {
+                !ancestor.as_os_str().is_empty() && std::fs::symlink_metadata(ancestor).is_err()
+            })
+            .last()
+            .unwrap_or(path);
+
+        Checkpoint::of(&[created])
+    }
+
+    /// Puts the captured paths back as they were
+    pub fn revert(&self) -> Result<()> {
+        for (path, state) in self.snapshots.iter().rev() {
+            state
+                .restore(path)
+                .with_context(|| format!("Failed to revert {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl State {
+    fn take(path: &Path) -> Result<State> {
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(State::Missing),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(if metadata.is_symlink() {
+            State::Symlink {
+                target: std::fs::read_link(path)?,
+            }
+        } else if metadata.is_dir() {
+            State::Directory {
+                permissions: metadata.permissions(),
+                owner: owner(&metadata),
+            }
+        } else {
+            State::File {
+                contents: std::fs::read(path)?,
+                permissions: metadata.permissions(),
+                owner: owner(&metadata),
+            }
+        })
+    }
+
+    fn restore(&self, path: &Path) -> Result<()> {
+        let current = std::fs::symlink_metadata(path).ok();
+
+        match self {
+            State::Missing => match current {
+                // Everything within was created after the checkpoint
+                Some(current) if current.is_dir() => std::fs::remove_dir_all(path)?,
+                Some(_) => std::fs::remove_file(path)?,
+                None => (),
+            },
+            State::File {
+                contents,
+                permissions,
+                owner,
+            } => {
+                if current.is_some_and(|current| !current.is_file()) {
+                    remove(path)?;
+                }
+
+                std::fs::write(path, contents)?;
+                std::fs::set_permissions(path, permissions.clone())?;
+                set_owner(path, *owner)?;
+            }
+            State::Symlink { target } => {
+                if current.is_some() {
+                    remove(path)?;
+                }
+
+                symlink(target, path)?;
+            }
+            State::Directory { permissions, owner } => {
+                if current.is_some_and(|current| !current.is_dir()) {
+                    remove(path)?;
+                }
+
+                std::fs::create_dir_all(path)?;
+                std::fs::set_permissions(path, permissions.clone())?;
+                set_owner(path, *owner)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn owner(metadata: &std::fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &std::fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+#[cfg(unix)]
+fn set_owner(path: &Path, owner: Option<(u32, u32)>) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Some((uid, gid)) = owner else {
+        return Ok(());
+    };
+
+    let metadata = std::fs::metadata(path)?;
+
+    // Only root can give files away, so leave unchanged owners alone
+    if (metadata.uid(), metadata.gid()) == (uid, gid) {
+        return Ok(());
+    }
+
+    std::os::unix::fs::chown(path, Some(uid), Some(gid))
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _owner: Option<(u32, u32)>) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_reverts_files_links_and_directories() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        let link = dir.path().join("link");
+        let created = dir.path().join("a");
+
+        std::fs::write(&file, "before")?;
+        #[cfg(unix)]
+        symlink(&file, &link)?;
+
+        let checkpoint = Checkpoint::of(&[&file, &link])?;
+        let created_checkpoint = Checkpoint::of_created_dir(&created.join("b/c"))?;
+
+        std::fs::write(&file, "after")?;
+        let _ = std::fs::remove_file(&link);
+        std::fs::create_dir_all(created.join("b/c"))?;
+
+        created_checkpoint.revert()?;
+        checkpoint.revert()?;
+
+        assert_eq!("before", std::fs::read_to_string(&file)?);
+        assert!(!created.exists());
+        #[cfg(unix)]
+        assert_eq!(file, std::fs::read_link(&link)?);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
 
 use super::super::Atom;
 use std::path::PathBuf;
@@ -25,6 +25,10 @@ impl Atom for Create {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of_created_dir(&self.path)?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
         std::fs::create_dir_all(&self.path)?;
 
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use tracing::error;
 
-use crate::atoms::{Atom, Outcome};
+use crate::atoms::{Atom, Checkpoint, Outcome};
 
 pub struct Remove {
     pub target: PathBuf,
@@ -53,7 +53,12 @@ impl Atom for Remove {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.target])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
+        crate::backups::backup(&self.target)?;
         std::fs::remove_dir(&self.target)?;
         Ok(())
     }
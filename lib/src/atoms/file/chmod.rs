@@ -67,6 +67,10 @@ impl Atom for Chmod {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<crate::atoms::Checkpoint>> {
+        Ok(Some(crate::atoms::Checkpoint::of(&[&self.path])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
         std::fs::set_permissions(
             self.path.as_path(),
@@ -122,6 +122,10 @@ impl Atom for Chown {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<crate::atoms::Checkpoint>> {
+        Ok(Some(crate::atoms::Checkpoint::of(&[&self.path])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
         if !self.owner.is_empty() {
             self.path.set_owner(self.owner.as_str())?;
@@ -1,4 +1,4 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
 
 use super::super::Atom;
 use super::FileAtom;
@@ -58,7 +58,12 @@ impl Atom for SetContents {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.path])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
+        crate::backups::backup(&self.path)?;
         std::fs::write(&self.path, &self.contents)?;
 
         Ok(())
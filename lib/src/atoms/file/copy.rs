@@ -1,4 +1,4 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
 
 use super::super::Atom;
 use super::FileAtom;
@@ -48,7 +48,12 @@ impl Atom for Copy {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.to])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
+        crate::backups::backup(&self.to)?;
         std::fs::copy(&self.from, &self.to)?;
 
         Ok(())
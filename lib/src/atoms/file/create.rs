@@ -1,4 +1,4 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
 
 use super::super::Atom;
 use super::FileAtom;
@@ -28,6 +28,10 @@ impl Atom for Create {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.path])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
         std::fs::File::create(&self.path)?;
 
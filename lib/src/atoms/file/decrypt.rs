@@ -1,19 +1,37 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
 
 use super::super::Atom;
 use super::FileAtom;
-use age::armor::ArmoredReader;
-use age::secrecy::SecretString;
-use std::io::Read;
+use crate::encryption;
 use std::path::PathBuf;
 use tracing::error;
 
 pub struct Decrypt {
     pub encrypted_content: Vec<u8>,
-    pub passphrase: String,
+    pub key: DecryptionKey,
     pub path: PathBuf,
 }
 
+/// What the content was encrypted with
+pub enum DecryptionKey {
+    Passphrase(String),
+    /// Paths of age identity files and SSH private keys
+    Identities(Vec<String>),
+}
+
+impl DecryptionKey {
+    pub fn decrypt(&self, encrypted_content: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            DecryptionKey::Passphrase(passphrase) => {
+                encryption::decrypt_with_passphrase(passphrase, encrypted_content)
+            }
+            DecryptionKey::Identities(paths) => {
+                encryption::decrypt(&encryption::load_identities(paths)?, encrypted_content)
+            }
+        }
+    }
+}
+
 impl FileAtom for Decrypt {
     fn get_path(&self) -> &PathBuf {
         &self.path
@@ -41,8 +59,8 @@ impl Atom for Decrypt {
             });
         }
 
-        // Decrypting file with provided passphrase makes plan work
-        match decrypt(&self.passphrase, &self.encrypted_content) {
+        // Decrypting file with provided key makes plan work
+        match self.key.decrypt(&self.encrypted_content) {
             Ok(_) => Ok(Outcome {
                 side_effects: vec![],
                 should_run: true,
@@ -62,30 +80,20 @@ impl Atom for Decrypt {
         }
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.path])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
-        let decrypted_content = decrypt(&self.passphrase, &self.encrypted_content)?;
+        let decrypted_content = self.key.decrypt(&self.encrypted_content)?;
 
+        crate::backups::backup(&self.path)?;
         std::fs::write(&self.path, decrypted_content)?;
 
         Ok(())
     }
 }
 
-fn decrypt(passphrase: &str, encrypted_content: &[u8]) -> anyhow::Result<Vec<u8>> {
-    let decryptor = age::Decryptor::new(ArmoredReader::new(encrypted_content))?;
-    if !decryptor.is_scrypt() {
-        return Err(anyhow::anyhow!("Cannot create passphrase decryptor!"));
-    }
-
-    let mut decrypted = vec![];
-    let secret = SecretString::from(passphrase.to_owned());
-    let identity = age::scrypt::Identity::new(secret);
-    let mut reader = decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))?;
-    reader.read_to_end(&mut decrypted)?;
-
-    Ok(decrypted)
-}
-
 #[cfg(test)]
 mod tests {
     use tempfile::NamedTempFile;
@@ -109,7 +117,7 @@ mod tests {
         let decrypt = Decrypt {
             encrypted_content: encrypted_content.to_owned(),
             path: file.path().to_path_buf(),
-            passphrase,
+            key: DecryptionKey::Passphrase(passphrase),
         };
 
         // plan
@@ -119,7 +127,7 @@ mod tests {
         let another_decrypt = Decrypt {
             encrypted_content: encrypted_content.to_owned(),
             path: file.path().to_path_buf(),
-            passphrase: "fkbr".to_string(),
+            key: DecryptionKey::Passphrase("fkbr".to_string()),
         };
 
         // plan
@@ -143,7 +151,7 @@ mod tests {
         let mut decrypt = Decrypt {
             encrypted_content: encrypted_content.to_owned(),
             path: file.path().to_path_buf(),
-            passphrase,
+            key: DecryptionKey::Passphrase(passphrase),
         };
 
         // plan, execute
@@ -153,7 +161,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn it_can_decrypt_with_identities() -> anyhow::Result<()> {
+        use age::secrecy::ExposeSecret;
+
+        let identity = age::x25519::Identity::generate();
+        let mut identity_file = NamedTempFile::new()?;
+        identity_file.write_all(identity.to_string().expose_secret().as_bytes())?;
+
+        let recipients = encryption::parse_recipients(&[identity.to_public().to_string()])?;
+        let encrypted_content = encryption::encrypt(&recipients, b"Shol'va")?;
+
+        let file = NamedTempFile::new()?;
+
+        let mut decrypt = Decrypt {
+            encrypted_content,
+            path: file.path().to_path_buf(),
+            key: DecryptionKey::Identities(vec![identity_file.path().display().to_string()]),
+        };
+
+        assert_eq!(true, decrypt.plan().unwrap().should_run);
+        decrypt.execute()?;
+        assert_eq!("Shol'va", std::fs::read_to_string(file.path())?);
+
+        Ok(())
+    }
+
     fn encrypt(passphrase: String, content: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        use age::secrecy::SecretString;
+
         let secret = SecretString::from(passphrase);
         let encryptor = age::Encryptor::with_user_passphrase(secret);
 
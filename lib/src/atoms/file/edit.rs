@@ -0,0 +1,163 @@
+use crate::atoms::{Checkpoint, Outcome};
+
+use super::super::Atom;
+use super::FileAtom;
+use std::path::PathBuf;
+use tracing::{error, info};
+
+/// A change to part of a file's contents. Applying it to its own result
+/// doesn't change anything.
+pub trait ContentEdit: std::fmt::Display {
+    fn apply(&self, contents: &str) -> anyhow::Result<String>;
+}
+
+/// Edits a file in place, leaving the rest of its contents alone
+pub struct EditContents {
+    pub path: PathBuf,
+    pub edit: Box<dyn ContentEdit>,
+
+    /// Edit an empty file when it doesn't exist
+    pub create: bool,
+}
+
+impl FileAtom for EditContents {
+    fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+impl EditContents {
+    fn contents(&self) -> anyhow::Result<Option<String>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && self.create => {
+                Ok(Some(String::new()))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for EditContents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The file {} needs {}", self.path.display(), self.edit)
+    }
+}
+
+impl Atom for EditContents {
+    fn plan(&self) -> anyhow::Result<Outcome> {
+        let Some(contents) = self.contents()? else {
+            error!(
+                "Cannot plan: file doesn't exist, and isn't created: {}",
+                self.path.display()
+            );
+
+            return Ok(Outcome {
+                side_effects: vec![],
+                should_run: false,
+            });
+        };
+
+        let edited = self.edit.apply(&contents)?;
+
+        if edited == contents {
+            return Ok(Outcome {
+                side_effects: vec![],
+                should_run: false,
+            });
+        }
+
+        info!("{}\n{}", self.path.display(), diff(&contents, &edited));
+
+        Ok(Outcome {
+            side_effects: vec![],
+            should_run: true,
+        })
+    }
+
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.path])?))
+    }
+
+    fn execute(&mut self) -> anyhow::Result<()> {
+        let contents = self.contents()?.unwrap_or_default();
+        let edited = self.edit.apply(&contents)?;
+
+        crate::backups::backup(&self.path)?;
+        std::fs::write(&self.path, edited)?;
+
+        Ok(())
+    }
+}
+
+/// The changed lines between `before` and `after`, with a line of context
+fn diff(before: &str, after: &str) -> String {
+    let lines = diff::lines(before, after);
+
+    let changed = |index: usize| {
+        lines
+            .get(index)
+            .is_some_and(|line| !matches!(line, diff::Result::Both(..)))
+    };
+
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            changed(*index) || changed(index + 1) || index.checked_sub(1).is_some_and(changed)
+        })
+        .map(|(_, line)| match line {
+            diff::Result::Left(line) => format!("-{line}"),
+            diff::Result::Right(line) => format!("+{line}"),
+            diff::Result::Both(line, _) => format!(" {line}"),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Append(&'static str);
+
+    impl std::fmt::Display for Append {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{} appended", self.0)
+        }
+    }
+
+    impl ContentEdit for Append {
+        fn apply(&self, contents: &str) -> anyhow::Result<String> {
+            match contents.lines().any(|line| line == self.0) {
+                true => Ok(contents.to_string()),
+                false => Ok(format!("{contents}{}\n", self.0)),
+            }
+        }
+    }
+
+    #[test]
+    fn it_can() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hosts");
+
+        let mut atom = EditContents {
+            path: path.clone(),
+            edit: Box::new(Append("127.0.0.1 abydos")),
+            create: false,
+        };
+        assert_eq!(false, atom.plan()?.should_run);
+
+        atom.create = true;
+        assert_eq!(true, atom.plan()?.should_run);
+        atom.execute()?;
+        assert_eq!(false, atom.plan()?.should_run);
+        assert_eq!("127.0.0.1 abydos\n", std::fs::read_to_string(&path)?);
+
+        assert_eq!(" a\n-b\n+c\n d", diff("a\nb\nd\ne\n", "a\nc\nd\ne\n"));
+
+        Ok(())
+    }
+}
@@ -1,13 +1,46 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
+use crate::utilities::remove;
 
 use super::super::Atom;
 use super::FileAtom;
-use std::path::PathBuf;
-use tracing::{error, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
 
 pub struct Link {
     pub source: PathBuf,
     pub target: PathBuf,
+    pub on_conflict: OnConflict,
+    aside: OnceCell<PathBuf>,
+}
+
+/// What to do when the target exists and isn't a link
+#[derive(JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// Leave the target alone and don't link
+    #[default]
+    Skip,
+    /// Move the target aside, with a timestamp suffix
+    Backup,
+    /// Remove the target
+    Overwrite,
+    /// Move the target to the source, so it's managed from now on
+    Adopt,
+}
+
+impl std::fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnConflict::Skip => write!(f, "skipped"),
+            OnConflict::Backup => write!(f, "moved aside"),
+            OnConflict::Overwrite => write!(f, "overwritten"),
+            OnConflict::Adopt => write!(f, "adopted"),
+        }
+    }
 }
 
 impl FileAtom for Link {
@@ -16,6 +49,134 @@ impl FileAtom for Link {
     }
 }
 
+impl Link {
+    pub fn new(source: PathBuf, target: PathBuf, on_conflict: OnConflict) -> Link {
+        Link {
+            source,
+            target,
+            on_conflict,
+            aside: OnceCell::new(),
+        }
+    }
+
+    /// Where a conflicting target is moved aside to, decided once so the
+    /// checkpoint captures the same path the target is moved to
+    fn aside(&self) -> &Path {
+        self.aside.get_or_init(|| aside_path(&self.target))
+    }
+
+    /// Why `on_conflict` can't be carried out, when it would remove a
+    /// directory. Backups and checkpoints don't keep what's in directories.
+    fn refusal(&self) -> Option<String> {
+        if !self.conflicts() {
+            return None;
+        }
+
+        match self.on_conflict {
+            OnConflict::Overwrite if self.target.is_dir() => Some(format!(
+                "Won't overwrite the directory {}, set on_conflict to backup to move it aside",
+                self.target.display()
+            )),
+            OnConflict::Adopt
+                if std::fs::symlink_metadata(&self.source)
+                    .is_ok_and(|metadata| metadata.is_dir()) =>
+            {
+                Some(format!(
+                    "Won't replace the directory {} by adopting {}",
+                    self.source.display(),
+                    self.target.display()
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the target exists and isn't a link
+    fn conflicts(&self) -> bool {
+        std::fs::symlink_metadata(&self.target).is_ok_and(|metadata| !metadata.is_symlink())
+    }
+
+    /// Plan only allows replacing links, which are backed up first
+    fn replace_link(&self) -> anyhow::Result<()> {
+        if std::fs::symlink_metadata(&self.target).is_ok_and(|metadata| metadata.is_symlink()) {
+            crate::backups::backup(&self.target)?;
+            std::fs::remove_file(&self.target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears a conflicting target the way `on_conflict` asks for
+    fn resolve_conflict(&self) -> anyhow::Result<()> {
+        if !self.conflicts() {
+            return Ok(());
+        }
+
+        if let Some(refusal) = self.refusal() {
+            anyhow::bail!(refusal);
+        }
+
+        match self.on_conflict {
+            OnConflict::Skip => (),
+            OnConflict::Backup => {
+                let aside = self.aside();
+                info!(
+                    "Moving {} aside to {}",
+                    self.target.display(),
+                    aside.display()
+                );
+                std::fs::rename(&self.target, aside)?;
+            }
+            OnConflict::Overwrite => {
+                crate::backups::backup(&self.target)?;
+                remove(&self.target)?;
+            }
+            OnConflict::Adopt => {
+                if std::fs::symlink_metadata(&self.source).is_ok() {
+                    crate::backups::backup(&self.source)?;
+                    remove(&self.source)?;
+                }
+
+                if let Some(parent) = self.source.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+
+                info!(
+                    "Adopting {} as {}",
+                    self.target.display(),
+                    self.source.display()
+                );
+                std::fs::rename(&self.target, &self.source)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `path` with a timestamp suffix, that doesn't exist yet
+fn aside_path(path: &Path) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let mut aside = path.as_os_str().to_owned();
+    aside.push(format!(".{timestamp}"));
+
+    let mut candidate = PathBuf::from(&aside);
+    let mut count = 1;
+
+    while std::fs::symlink_metadata(&candidate).is_ok() {
+        let mut numbered = aside.clone();
+        numbered.push(format!(".{count}"));
+        candidate = PathBuf::from(numbered);
+        count += 1;
+    }
+
+    candidate
+}
+
 impl std::fmt::Display for Link {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -23,14 +184,23 @@ impl std::fmt::Display for Link {
             "The file {} contents needs to be linked from {}",
             self.target.display(),
             self.source.display(),
-        )
+        )?;
+
+        if self.on_conflict != OnConflict::Skip && self.conflicts() {
+            write!(f, ", the existing file will be {}", self.on_conflict)?;
+        }
+
+        Ok(())
     }
 }
 
 impl Atom for Link {
     fn plan(&self) -> anyhow::Result<Outcome> {
-        // First, ensure source exists and can be linked to
-        if !self.source.exists() {
+        let conflicts = self.conflicts();
+        let adopting = conflicts && self.on_conflict == OnConflict::Adopt;
+
+        // First, ensure source exists and can be linked to, unless it's adopted
+        if !adopting && !self.source.exists() {
             error!(
                 "Cannot plan: source file is missing: {}",
                 self.source.display()
@@ -49,16 +219,40 @@ impl Atom for Link {
                 should_run: true,
             });
         }
+        // Target file exists and isn't a link, so it's up to on_conflict
+        if conflicts {
+            if let Some(refusal) = self.refusal() {
+                error!("Cannot plan: {}", refusal);
+
+                return Ok(Outcome {
+                    side_effects: vec![],
+                    should_run: false,
+                });
+            }
+
+            if self.on_conflict == OnConflict::Skip {
+                warn!(
+                    "Cannot plan: target already exists and isn't a link: {}. Set on_conflict to replace it",
+                    self.target.display()
+                );
+            } else {
+                info!(
+                    "Target already exists and isn't a link, it will be {}: {}",
+                    self.on_conflict,
+                    self.target.display()
+                );
+            }
+
+            return Ok(Outcome {
+                side_effects: vec![],
+                should_run: self.on_conflict != OnConflict::Skip,
+            });
+        }
+
         // Target file exists, lets check if it's a symlink which can be safely updated
-        // or return a false and emit some logging that we can't create the link
-        // without purging a file
         let link = match std::fs::read_link(&self.target) {
             Ok(link) => link,
             Err(err) => {
-                warn!(
-                    "Cannot plan: target already exists and isn't a link: {}",
-                    self.target.display()
-                );
                 error!("Cannot plan: {}", err);
 
                 return Ok(Outcome {
@@ -82,8 +276,27 @@ impl Atom for Link {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        // Only directories themselves are captured, not what's in them
+        if self.conflicts() && self.target.is_dir() {
+            return Ok(None);
+        }
+
+        match self.on_conflict {
+            OnConflict::Adopt => Ok(Some(Checkpoint::of(&[&self.source, &self.target])?)),
+            // The target is moved aside, so reverting removes the aside copy
+            OnConflict::Backup if self.conflicts() => Ok(Some(Checkpoint::of(&[
+                self.target.as_path(),
+                self.aside(),
+            ])?)),
+            _ => Ok(Some(Checkpoint::of(&[&self.target])?)),
+        }
+    }
+
     #[cfg(unix)]
     fn execute(&mut self) -> anyhow::Result<()> {
+        self.resolve_conflict()?;
+        self.replace_link()?;
         std::os::unix::fs::symlink(&self.source, &self.target)?;
 
         Ok(())
@@ -91,6 +304,9 @@ impl Atom for Link {
 
     #[cfg(windows)]
     fn execute(&mut self) -> anyhow::Result<()> {
+        self.resolve_conflict()?;
+        self.replace_link()?;
+
         if self.target.is_dir() {
             std::os::windows::fs::symlink_dir(&self.source, &self.target)?;
         } else {
@@ -124,12 +340,117 @@ mod tests {
             }
         };
 
-        let mut atom = Link {
-            target: from_dir.path().join("symlink"),
-            source: to_file.path().to_path_buf(),
-        };
+        let mut atom = Link::new(
+            to_file.path().to_path_buf(),
+            from_dir.path().join("symlink"),
+            OnConflict::Skip,
+        );
         assert_eq!(true, atom.plan().unwrap().should_run);
         assert_eq!(true, atom.execute().is_ok());
         assert_eq!(false, atom.plan().unwrap().should_run);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_resolves_conflicts() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("files").join("bashrc");
+        let target = dir.path().join(".bashrc");
+
+        let link = |on_conflict| Link::new(source.clone(), target.clone(), on_conflict);
+
+        // Adopting moves the target into the source before linking
+        std::fs::write(&target, "adopted")?;
+        assert_eq!(false, link(OnConflict::Skip).plan()?.should_run);
+
+        let mut atom = link(OnConflict::Adopt);
+        assert_eq!(true, atom.plan()?.should_run);
+        atom.execute()?;
+        assert_eq!("adopted", std::fs::read_to_string(&source)?);
+        assert_eq!(source, std::fs::read_link(&target)?);
+
+        // Backing up moves the target aside
+        std::fs::remove_file(&target)?;
+        std::fs::write(&target, "moved aside")?;
+        link(OnConflict::Backup).execute()?;
+        assert_eq!(source, std::fs::read_link(&target)?);
+
+        let aside: Vec<String> = std::fs::read_dir(dir.path())?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(".bashrc."))
+            .collect();
+        assert_eq!(1, aside.len());
+        assert_eq!(
+            "moved aside",
+            std::fs::read_to_string(dir.path().join(&aside[0]))?
+        );
+
+        // Overwriting removes the target
+        std::fs::remove_file(&target)?;
+        std::fs::write(&target, "overwritten")?;
+        link(OnConflict::Overwrite).execute()?;
+        assert_eq!(source, std::fs::read_link(&target)?);
+        assert_eq!("adopted", std::fs::read_to_string(&target)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_keeps_conflicting_directories() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("files").join("config");
+        let target = dir.path().join(".config");
+
+        std::fs::create_dir_all(target.join("nvim"))?;
+        std::fs::write(target.join("nvim").join("init.lua"), "kept")?;
+        std::fs::create_dir_all(&source)?;
+        std::fs::write(source.join("managed"), "kept")?;
+
+        // Overwriting would remove the directory with everything in it
+        let mut atom = Link::new(source.clone(), target.clone(), OnConflict::Overwrite);
+        assert_eq!(false, atom.plan()?.should_run);
+        assert!(atom.execute().is_err());
+
+        // Adopting would replace the source directory
+        let mut atom = Link::new(source.clone(), target.clone(), OnConflict::Adopt);
+        assert_eq!(false, atom.plan()?.should_run);
+        assert!(atom.execute().is_err());
+
+        assert_eq!(
+            "kept",
+            std::fs::read_to_string(target.join("nvim").join("init.lua"))?
+        );
+        assert_eq!("kept", std::fs::read_to_string(source.join("managed"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_reverts_moving_aside() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("bashrc");
+        let target = dir.path().join(".bashrc");
+
+        std::fs::write(&source, "managed")?;
+        std::fs::write(&target, "moved aside")?;
+
+        let mut atom = Link::new(source.clone(), target.clone(), OnConflict::Backup);
+        let checkpoint = atom.checkpoint()?.expect("a checkpoint");
+        atom.execute()?;
+        assert_eq!(source, std::fs::read_link(&target)?);
+
+        checkpoint.revert()?;
+        assert_eq!("moved aside", std::fs::read_to_string(&target)?);
+
+        let names: Vec<_> = std::fs::read_dir(dir.path())?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name())
+            .collect();
+        assert_eq!(2, names.len());
+
+        Ok(())
+    }
 }
@@ -4,6 +4,7 @@ mod contents;
 mod copy;
 mod create;
 mod decrypt;
+mod edit;
 mod link;
 mod remove;
 mod unarchive;
@@ -14,10 +15,11 @@ pub use chown::Chown;
 pub use contents::SetContents;
 pub use copy::Copy;
 pub use create::Create;
-pub use decrypt::Decrypt;
-pub use link::Link;
+pub use decrypt::{Decrypt, DecryptionKey};
+pub use edit::{ContentEdit, EditContents};
+pub use link::{Link, OnConflict};
 pub use remove::Remove;
-pub use unarchive::Unarchive;
+pub use unarchive::{MemberFilter, Unarchive};
 
 pub trait FileAtom: Atom {
     // Don't think this is needed? Validate soon
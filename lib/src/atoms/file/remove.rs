@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use tracing::error;
 
-use crate::atoms::{Atom, Outcome};
+use crate::atoms::{Atom, Checkpoint, Outcome};
 
 use super::FileAtom;
 
@@ -24,7 +24,11 @@ impl std::fmt::Display for Remove {
 
 impl Atom for Remove {
     fn plan(&self) -> anyhow::Result<crate::atoms::Outcome> {
-        if !self.target.is_file() {
+        // Links are removed themselves, even when they're dangling
+        let is_link =
+            std::fs::symlink_metadata(&self.target).is_ok_and(|metadata| metadata.is_symlink());
+
+        if !is_link && !self.target.is_file() {
             error!(
                 "Cannot plan: target isn`t a file: {}",
                 self.target.display()
@@ -71,7 +75,12 @@ impl Atom for Remove {
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.target])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
+        crate::backups::backup(&self.target)?;
         std::fs::remove_file(&self.target)?;
         Ok(())
     }
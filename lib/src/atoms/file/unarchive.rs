@@ -1,8 +1,14 @@
-use std::{fs::File, path::PathBuf};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
 
+use anyhow::anyhow;
 use flate2::read::GzDecoder;
-use tar::Archive;
+use globset::GlobSet;
+use tar::{Archive, EntryType};
+use tracing::warn;
 
+use crate::actions::glob_set;
 use crate::atoms::{Atom, Outcome};
 
 use super::FileAtom;
@@ -10,7 +16,289 @@ use super::FileAtom;
 pub struct Unarchive {
     pub origin: PathBuf,
     pub dest: PathBuf,
-    pub force: bool,
+    /// Whether to extract when `dest` exists. When set, it's extracted
+    /// every time, even when it was extracted before
+    pub force: Option<bool>,
+    pub filter: MemberFilter,
+}
+
+/// Which members of an archive are extracted, and where
+#[derive(Default)]
+pub struct MemberFilter {
+    /// Leading path components removed from every member
+    strip_components: usize,
+
+    /// When not empty, only members matching these are extracted
+    include: GlobSet,
+
+    exclude: GlobSet,
+
+    /// The options as given, so changing them extracts again
+    options: String,
+}
+
+impl MemberFilter {
+    pub fn new(
+        strip_components: usize,
+        include: &[String],
+        exclude: &[String],
+    ) -> anyhow::Result<MemberFilter> {
+        Ok(MemberFilter {
+            strip_components,
+            include: glob_set(include, "file.unarchive")?,
+            exclude: glob_set(exclude, "file.unarchive")?,
+            options: format!("{strip_components}\n{include:?}\n{exclude:?}"),
+        })
+    }
+
+    /// Where a member is extracted to within the destination, if at all.
+    /// Members that would end up outside of it are left out.
+    fn destination(&self, member: &Path) -> Option<PathBuf> {
+        if !self.include.is_empty() && !self.include.is_match(member) {
+            return None;
+        }
+
+        if self.exclude.is_match(member) {
+            return None;
+        }
+
+        let mut components = member.components().peekable();
+        while components.next_if(|c| c == &Component::CurDir).is_some() {}
+
+        let path: PathBuf = components.skip(self.strip_components).collect();
+
+        match path.components().all(|c| matches!(c, Component::Normal(_))) {
+            true if !path.as_os_str().is_empty() => Some(path),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Zip,
+    Tar,
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+}
+
+impl Format {
+    /// Tells the format by the magic bytes the file starts with
+    fn detect(path: &Path) -> anyhow::Result<Format> {
+        let mut magic = Vec::with_capacity(262);
+        File::open(path)?.take(262).read_to_end(&mut magic)?;
+
+        let format = match magic.as_slice() {
+            [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => Format::Zip,
+            [0x1f, 0x8b, ..] => Format::Gzip,
+            [0xfd, b'7', b'z', b'X', b'Z', 0, ..] => Format::Xz,
+            [b'B', b'Z', b'h', ..] => Format::Bzip2,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Format::Zstd,
+            _ if is_tar(&magic) => Format::Tar,
+            _ => return Err(anyhow!("{} isn't a supported archive", path.display())),
+        };
+
+        Ok(format)
+    }
+}
+
+fn is_tar(header: &[u8]) -> bool {
+    header.get(257..262) == Some(b"ustar")
+}
+
+/// Whether `target`, relative to `path` within the destination, stays
+/// within it. Absolute targets never do.
+fn stays_within(path: &Path, target: &Path) -> bool {
+    let mut depth = path.components().count().saturating_sub(1);
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/// Creates the parent directories of `path`, making sure that links among
+/// them don't lead outside of `dest`, and removes a link at `path` itself, so
+/// nothing is written through links
+fn prepare(dest: &Path, path: &Path) -> anyhow::Result<()> {
+    let outside = || anyhow!("{} leads outside of {}", path.display(), dest.display());
+
+    let Some(parent) = path.parent() else {
+        return Err(outside());
+    };
+
+    let existing = parent
+        .ancestors()
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .ok_or_else(outside)?;
+
+    if !existing.canonicalize()?.starts_with(dest) {
+        return Err(outside());
+    }
+
+    std::fs::create_dir_all(parent)?;
+
+    if !parent.canonicalize()?.starts_with(dest) {
+        return Err(outside());
+    }
+
+    if path
+        .symlink_metadata()
+        .is_ok_and(|metadata| metadata.is_symlink())
+    {
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+impl Unarchive {
+    /// Records the checksum of the archive extracted to the destination with
+    /// these filter options, so a changed archive or filter is extracted again
+    fn marker(&self) -> Option<PathBuf> {
+        let origin = std::path::absolute(&self.origin).ok()?;
+        let dest = std::path::absolute(&self.dest).ok()?;
+        let key = format!(
+            "{}\n{}\n{}",
+            origin.display(),
+            dest.display(),
+            self.filter.options
+        );
+
+        dirs_next::cache_dir().map(|dir| {
+            dir.join("comtrya")
+                .join("unarchive")
+                .join(format!("{}.sha256", sha256::digest(key)))
+        })
+    }
+
+    fn checksum(&self) -> anyhow::Result<String> {
+        sha256::try_digest(self.origin.as_path())
+            .map_err(|err| anyhow!("Failed to read {}: {}", self.origin.display(), err))
+    }
+
+    fn decompress(&self, format: Format) -> anyhow::Result<Box<dyn Read>> {
+        let file = BufReader::new(File::open(&self.origin)?);
+
+        Ok(match format {
+            Format::Gzip => Box::new(GzDecoder::new(file)),
+            Format::Xz => Box::new(xz2::read::XzDecoder::new(file)),
+            Format::Bzip2 => Box::new(bzip2::read::BzDecoder::new(file)),
+            Format::Zstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+            Format::Tar | Format::Zip => Box::new(file),
+        })
+    }
+
+    fn extract_tar(&self, reader: impl Read, dest: &Path) -> anyhow::Result<()> {
+        let mut archive = Archive::new(reader);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let member = entry.path()?.into_owned();
+
+            let Some(relative) = self.filter.destination(&member) else {
+                continue;
+            };
+            let path = dest.join(&relative);
+            let link = entry.link_name()?.map(|link| link.into_owned());
+
+            match (entry.header().entry_type(), link) {
+                (EntryType::Symlink, Some(target)) if !stays_within(&relative, &target) => {
+                    warn!(
+                        "Skipping {}, it links outside of {}",
+                        member.display(),
+                        dest.display()
+                    );
+                }
+                // Hard links name another member, which is moved like this one
+                (EntryType::Link, Some(target)) => {
+                    let Some(target) = self.filter.destination(&target) else {
+                        warn!(
+                            "Skipping {}, it links to a member that isn't extracted",
+                            member.display()
+                        );
+                        continue;
+                    };
+                    let target = dest.join(target);
+
+                    if !target.canonicalize()?.starts_with(dest) {
+                        return Err(anyhow!(
+                            "{} links outside of {}",
+                            member.display(),
+                            dest.display()
+                        ));
+                    }
+
+                    prepare(dest, &path)?;
+                    let _ = std::fs::remove_file(&path);
+                    std::fs::hard_link(target, &path)?;
+                }
+                _ => {
+                    prepare(dest, &path)?;
+                    entry.unpack(&path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn extract_zip(&self, dest: &Path) -> anyhow::Result<()> {
+        let mut archive = zip::ZipArchive::new(BufReader::new(File::open(&self.origin)?))?;
+
+        for index in 0..archive.len() {
+            let mut member = archive.by_index(index)?;
+
+            let Some(path) = member
+                .enclosed_name()
+                .and_then(|name| self.filter.destination(&name))
+            else {
+                continue;
+            };
+
+            let path = dest.join(path);
+            prepare(dest, &path)?;
+
+            if member.is_dir() {
+                std::fs::create_dir_all(&path)?;
+                continue;
+            }
+
+            std::io::copy(&mut member, &mut File::create(&path)?)?;
+
+            #[cfg(unix)]
+            if let Some(mode) = member.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A compressed file that isn't a tarball is extracted as the archive's
+    /// name, without its extension
+    fn extract_file(&self, mut reader: impl Read, dest: &Path) -> anyhow::Result<()> {
+        let name = PathBuf::from(self.origin.file_stem().unwrap_or_default());
+
+        if let Some(path) = self.filter.destination(&name) {
+            let path = dest.join(path);
+            prepare(dest, &path)?;
+
+            std::io::copy(&mut reader, &mut File::create(path)?)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl FileAtom for Unarchive {
@@ -22,31 +310,77 @@ impl FileAtom for Unarchive {
 impl Atom for Unarchive {
     // Determine if this atom needs to run
     fn plan(&self) -> anyhow::Result<Outcome> {
-        if self.dest.exists() {
-            if self.force {
-                return Ok(Outcome {
-                    side_effects: vec![],
-                    should_run: self.origin.exists(),
-                });
-            }
+        if !self.origin.exists() {
             return Ok(Outcome {
                 side_effects: vec![],
                 should_run: false,
             });
         }
 
+        if !self.dest.exists() {
+            return Ok(Outcome {
+                side_effects: vec![],
+                should_run: true,
+            });
+        }
+
+        if self.force == Some(true) {
+            return Ok(Outcome {
+                side_effects: vec![],
+                should_run: true,
+            });
+        }
+
+        // Extracted before: only again when the archive changed
+        let checksum = self
+            .marker()
+            .and_then(|marker| std::fs::read_to_string(marker).ok());
+
+        let should_run = match checksum {
+            Some(checksum) => checksum.trim() != self.checksum()?,
+            None => self.force.unwrap_or(true),
+        };
+
         Ok(Outcome {
             side_effects: vec![],
-            should_run: self.origin.exists(),
+            should_run,
         })
     }
 
     // Apply new to old
     fn execute(&mut self) -> anyhow::Result<()> {
-        let tar_gz = File::open(&self.origin)?;
-        let tar = GzDecoder::new(tar_gz);
-        let mut archive = Archive::new(tar);
-        archive.unpack(&self.dest)?;
+        let format = Format::detect(&self.origin)?;
+
+        // Members are only written below the resolved destination
+        std::fs::create_dir_all(&self.dest)?;
+        let dest = self.dest.canonicalize()?;
+
+        match format {
+            Format::Zip => self.extract_zip(&dest)?,
+            Format::Tar => self.extract_tar(self.decompress(format)?, &dest)?,
+            _ => {
+                let mut reader = self.decompress(format)?;
+
+                // Compressed tarballs are told apart by their first header
+                let mut header = Vec::with_capacity(512);
+                reader.by_ref().take(512).read_to_end(&mut header)?;
+                let reader = Cursor::new(header.clone()).chain(reader);
+
+                match is_tar(&header) {
+                    true => self.extract_tar(reader, &dest)?,
+                    false => self.extract_file(reader, &dest)?,
+                }
+            }
+        }
+
+        if let Some(marker) = self.marker() {
+            if let Some(parent) = marker.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(marker, self.checksum()?)?;
+        }
+
         Ok(())
     }
 }
@@ -62,3 +396,170 @@ impl std::fmt::Display for Unarchive {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    fn tarball(files: &[(&str, &str)]) -> anyhow::Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(vec![]);
+
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents.as_bytes())?;
+        }
+
+        Ok(builder.into_inner()?)
+    }
+
+    #[test]
+    fn it_extracts_formats_by_magic_bytes() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let tar = tarball(&[("tool-1.0/bin/tool", "#!/bin/sh"), ("tool-1.0/README", "")])?;
+
+        let mut xz = xz2::write::XzEncoder::new(vec![], 6);
+        xz.write_all(&tar)?;
+        let mut zst = zstd::stream::write::Encoder::new(vec![], 0)?;
+        zst.write_all(&tar)?;
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all(b"plain")?;
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file(
+            "tool-1.0/bin/tool",
+            zip::write::SimpleFileOptions::default(),
+        )?;
+        zip.write_all(b"#!/bin/sh")?;
+
+        let archives = [
+            ("tool.tar", tar.clone()),
+            ("tool.tar.xz", xz.finish()?),
+            ("tool.tar.zst", zst.finish()?),
+            ("tool", zip.finish()?.into_inner()),
+        ];
+
+        for (name, contents) in archives {
+            let origin = dir.path().join(name);
+            std::fs::write(&origin, contents)?;
+
+            let dest = dir.path().join(format!("{name}-out"));
+            let mut atom = Unarchive {
+                origin,
+                dest: dest.clone(),
+                force: Some(false),
+                filter: MemberFilter::new(1, &[], &[String::from("**/README")])?,
+            };
+
+            assert_eq!(true, atom.plan()?.should_run, "{name}");
+            atom.execute()?;
+            assert_eq!(
+                "#!/bin/sh",
+                std::fs::read_to_string(dest.join("bin/tool"))?,
+                "{name}"
+            );
+            assert!(!dest.join("README").exists(), "{name}");
+            assert_eq!(false, atom.plan()?.should_run, "{name}");
+        }
+
+        // Plain compressed files are extracted without their extension
+        let origin = dir.path().join("notes.gz");
+        std::fs::write(&origin, gz.finish()?)?;
+
+        let mut atom = Unarchive {
+            origin: origin.clone(),
+            dest: dir.path().join("notes"),
+            force: Some(false),
+            filter: MemberFilter::default(),
+        };
+        atom.execute()?;
+        assert_eq!(
+            "plain",
+            std::fs::read_to_string(dir.path().join("notes/notes"))?
+        );
+
+        // A changed archive is extracted again
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all(b"changed")?;
+        std::fs::write(&origin, gz.finish()?)?;
+        assert_eq!(true, atom.plan()?.should_run);
+        atom.execute()?;
+        assert_eq!(false, atom.plan()?.should_run);
+
+        // So is one extracted with other options
+        atom.force = None;
+        atom.filter = MemberFilter::new(0, &[], &[String::from("*.txt")])?;
+        assert_eq!(true, atom.plan()?.should_run);
+
+        // Forcing always extracts
+        atom.filter = MemberFilter::default();
+        atom.force = Some(true);
+        assert_eq!(true, atom.plan()?.should_run);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_never_writes_outside_of_the_destination() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let outside = dir.path().join("outside");
+        std::fs::create_dir(&outside)?;
+
+        let mut builder = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "tool/bin", std::io::empty())?;
+
+        for (path, target) in [
+            ("tool/lib", outside.to_str().unwrap()),
+            ("tool/etc", "../../outside"),
+            ("tool/current", "bin"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, path, target)?;
+        }
+        for path in ["tool/lib/cron.d/x", "tool/etc/x", "tool/current/x"] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, "Goa'u".as_bytes())?;
+        }
+
+        let origin = dir.path().join("tool.tar");
+        std::fs::write(&origin, builder.into_inner()?)?;
+
+        let dest = dir.path().join("dest");
+        let mut atom = Unarchive {
+            origin,
+            dest: dest.clone(),
+            force: Some(false),
+            filter: MemberFilter::new(1, &[], &[])?,
+        };
+        atom.execute()?;
+
+        assert_eq!(0, std::fs::read_dir(&outside)?.count());
+        assert!(!dest.join("lib").is_symlink());
+        assert!(!dest.join("etc").is_symlink());
+        assert_eq!("Goa'u", std::fs::read_to_string(dest.join("lib/cron.d/x"))?);
+        assert_eq!("Goa'u", std::fs::read_to_string(dest.join("bin/x"))?);
+
+        // Links already in the destination aren't followed either
+        std::fs::remove_dir_all(dest.join("lib"))?;
+        std::os::unix::fs::symlink(&outside, dest.join("lib"))?;
+        assert!(atom.execute().is_err());
+        assert_eq!(0, std::fs::read_dir(&outside)?.count());
+
+        Ok(())
+    }
+}
@@ -1,12 +1,220 @@
-use crate::atoms::Outcome;
+use crate::atoms::{Checkpoint, Outcome};
+use crate::contexts::Contexts;
+use crate::http::{self, Request};
+use crate::tera_functions::render;
 
 use super::super::Atom;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::File;
 use std::io::Write;
-use std::{fs::File, path::PathBuf};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
 
+#[derive(Default)]
 pub struct Download {
     pub url: String,
     pub to: PathBuf,
+
+    /// When set, the downloaded content is rendered as a Tera template
+    pub template: Option<Contexts>,
+
+    /// The download must match this, and so must the local file
+    pub checksum: Option<Checksum>,
+
+    /// Download again once the local file is older than this
+    pub ttl: Option<Duration>,
+
+    /// Download again when the ETag or Last-Modified of the URL changed
+    pub revalidate: bool,
+
+    /// Headers, credentials, timeout and retries of the requests
+    pub request: Request,
+}
+
+/// Where the expected checksum comes from
+pub enum Checksum {
+    Sha256(String),
+    Sha512(String),
+    /// A checksum file, either a single checksum or lines of a checksum and
+    /// the file it's for, like `sha256sum` writes them
+    Url(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Algorithm {
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Checksums are hex, so their length tells the algorithm
+    fn of(checksum: &str) -> anyhow::Result<Algorithm> {
+        match checksum.len() {
+            64 => Ok(Algorithm::Sha256),
+            128 => Ok(Algorithm::Sha512),
+            _ => Err(anyhow!("{checksum} isn't a sha256 or sha512 checksum")),
+        }
+    }
+
+    fn digest(&self, content: &[u8]) -> String {
+        let digest = match self {
+            Algorithm::Sha256 => Sha256::digest(content).to_vec(),
+            Algorithm::Sha512 => Sha512::digest(content).to_vec(),
+        };
+
+        digest.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+/// Validators of the last download, to ask the server whether it changed
+#[derive(Default, Debug, PartialEq, Eq, Deserialize, Serialize)]
+struct Validators {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    fn path(to: &Path) -> Option<PathBuf> {
+        let to = std::path::absolute(to).ok()?;
+
+        dirs_next::cache_dir().map(|dir| {
+            dir.join("comtrya")
+                .join("downloads")
+                .join(format!("{}.json", sha256::digest(to.display().to_string())))
+        })
+    }
+
+    fn of(url: &str, headers: &reqwest::header::HeaderMap) -> Validators {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+
+        Validators {
+            url: url.to_string(),
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+
+    fn load(to: &Path) -> Option<Validators> {
+        let contents = std::fs::read_to_string(Validators::path(to)?).ok()?;
+
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn save(&self, to: &Path) -> anyhow::Result<()> {
+        if let Some(path) = Validators::path(to) {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(path, serde_json::to_string(self)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+impl Download {
+    pub fn new(url: String, to: PathBuf) -> Download {
+        Download {
+            url,
+            to,
+            ..Default::default()
+        }
+    }
+
+    /// The algorithm and checksum the download has to match
+    fn expected(&self) -> anyhow::Result<Option<(Algorithm, String)>> {
+        let expected = match &self.checksum {
+            None => return Ok(None),
+            Some(Checksum::Sha256(checksum)) => (Algorithm::Sha256, checksum.to_lowercase()),
+            Some(Checksum::Sha512(checksum)) => (Algorithm::Sha512, checksum.to_lowercase()),
+            Some(Checksum::Url(url)) => {
+                let checksums = http::get(url, &self.request)?.text()?;
+                let checksum = find_checksum(&checksums, &self.url).ok_or_else(|| {
+                    anyhow!("Failed to find the checksum of {} in {}", self.url, url)
+                })?;
+
+                (Algorithm::of(&checksum)?, checksum)
+            }
+        };
+
+        Ok(Some(expected))
+    }
+
+    /// Where the download is written to before it's renamed into place
+    fn partial(&self) -> PathBuf {
+        let name = self.to.file_name().unwrap_or_default().to_string_lossy();
+
+        self.to.with_file_name(format!(".{name}.comtrya-partial"))
+    }
+
+    fn is_expired(&self) -> bool {
+        let Some(ttl) = self.ttl else {
+            return false;
+        };
+
+        std::fs::metadata(&self.to)
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default()
+                    > ttl
+            })
+            .unwrap_or(true)
+    }
+
+    /// Asks the server whether the file changed since it was downloaded
+    fn changed_upstream(&self) -> anyhow::Result<bool> {
+        let known = match Validators::load(&self.to) {
+            Some(known) if known.url == self.url && !known.is_empty() => known,
+            _ => return Ok(true),
+        };
+
+        let response = http::head(&self.url, &self.request)?;
+        let current = Validators::of(&self.url, response.headers());
+
+        Ok(current.is_empty() || current != known)
+    }
+}
+
+/// The checksum for the file of `url` in a checksum file
+fn find_checksum(checksums: &str, url: &str) -> Option<String> {
+    let file_name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|url| url.rsplit('/').next())
+        .unwrap_or(url);
+
+    let lines: Vec<Vec<&str>> = checksums
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .filter(|fields: &Vec<&str>| !fields.is_empty())
+        .collect();
+
+    let checksum = match lines.as_slice() {
+        [line] if line.len() == 1 => line[0],
+        lines => lines.iter().find_map(|line| match line.as_slice() {
+            // Binary mode marks the file name with a star
+            [checksum, name, ..] if name.trim_start_matches('*') == file_name => Some(*checksum),
+            _ => None,
+        })?,
+    };
+
+    Some(checksum.to_lowercase())
 }
 
 impl std::fmt::Display for Download {
@@ -17,23 +225,88 @@ impl std::fmt::Display for Download {
 
 impl Atom for Download {
     fn plan(&self) -> anyhow::Result<Outcome> {
-        // Initial implementation will return false if the local file
-        // doesn't exist. I'd like to include a SHA to verify the
-        // correct version exists; or perhaps a TTL when omitted?
+        let should_run = if !self.to.exists() {
+            true
+        } else if self.is_expired() {
+            info!("{} is older than its ttl", self.to.display());
+            true
+        } else if let Some((algorithm, expected)) = self
+            .expected()?
+            // Rendered templates don't match the checksum of the download
+            .filter(|_| self.template.is_none())
+        {
+            let matches = algorithm.digest(&std::fs::read(&self.to)?) == expected;
+
+            if !matches {
+                info!("{} doesn't match its checksum", self.to.display());
+            }
+
+            !matches
+        } else if self.revalidate {
+            let changed = self.changed_upstream()?;
+
+            if changed {
+                info!("{} changed upstream", self.url);
+            }
+
+            changed
+        } else {
+            false
+        };
 
         Ok(Outcome {
             side_effects: vec![],
-            should_run: !PathBuf::from(&self.to).exists(),
+            should_run,
         })
     }
 
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(Some(Checkpoint::of(&[&self.to])?))
+    }
+
     fn execute(&mut self) -> anyhow::Result<()> {
-        let response = reqwest::blocking::get(&self.url)?;
+        let response = http::get(&self.url, &self.request)?;
+        let validators = Validators::of(&self.url, response.headers());
+        let content = response.bytes()?;
 
-        let mut file = File::create(&self.to)?;
+        if let Some((algorithm, expected)) = self.expected()? {
+            let actual = algorithm.digest(&content);
 
-        let content = response.bytes()?;
-        file.write_all(&content)?;
+            if actual != expected {
+                return Err(anyhow!(
+                    "Checksum of {} doesn't match, expected {} but got {}",
+                    self.url,
+                    expected,
+                    actual
+                ));
+            }
+        }
+
+        // Render before creating the file, so a failed render doesn't leave
+        // behind a file that marks the download as done
+        let content = match &self.template {
+            Some(contexts) => render(std::str::from_utf8(&content)?, contexts)?.into_bytes(),
+            None => content.to_vec(),
+        };
+
+        // Written next to the file and renamed over it, so the file is never
+        // left half written
+        let partial = self.partial();
+        let written = File::create(&partial).and_then(|mut file| file.write_all(&content));
+
+        if let Err(err) = written {
+            let _ = std::fs::remove_file(&partial);
+            return Err(err.into());
+        }
+
+        crate::backups::backup(&self.to)?;
+        std::fs::rename(&partial, &self.to)?;
+
+        if self.revalidate {
+            if let Err(err) = validators.save(&self.to) {
+                debug!("Failed to save validators of {}: {:?}", self.url, err);
+            }
+        }
 
         Ok(())
     }
@@ -45,15 +318,19 @@ mod tests {
     use pretty_assertions::assert_eq;
     use tempfile::tempdir;
 
+    fn serve(body: &str) -> String {
+        crate::http::tests::serve(&[(200, body)]).0
+    }
+
     #[test]
     fn it_can() {
         let tmpdir = tempdir().unwrap();
         let to_file = tmpdir.path().join("download");
 
-        let mut atom = Download {
-            url: String::from("https://www.google.com/images/branding/googlelogo/2x/googlelogo_color_272x92dp.png"),
-            to: to_file,
-        };
+        let mut atom = Download::new(
+            String::from("https://www.google.com/images/branding/googlelogo/2x/googlelogo_color_272x92dp.png"),
+            to_file,
+        );
 
         assert_eq!(true, atom.plan().unwrap().should_run);
 
@@ -61,4 +338,66 @@ mod tests {
         assert_eq!(true, result.is_ok());
         assert_eq!(false, atom.plan().unwrap().should_run);
     }
+
+    #[test]
+    fn it_verifies_checksums() -> anyhow::Result<()> {
+        let url = serve("chevron seven locked");
+        let tmpdir = tempdir()?;
+        let to = tmpdir.path().join("gate.txt");
+
+        let checksum = Algorithm::Sha256.digest(b"chevron seven locked");
+
+        let mut atom = Download {
+            checksum: Some(Checksum::Sha512(Algorithm::Sha512.digest(b"other"))),
+            ..Download::new(format!("{url}/gate.txt"), to.clone())
+        };
+        assert!(atom.execute().is_err());
+        assert!(!to.exists());
+
+        let mut atom = Download {
+            checksum: Some(Checksum::Sha256(checksum.clone())),
+            ..Download::new(format!("{url}/gate.txt"), to.clone())
+        };
+        atom.execute()?;
+        assert_eq!("chevron seven locked", std::fs::read_to_string(&to)?);
+        assert_eq!(false, atom.plan()?.should_run);
+
+        // A changed local file is downloaded again
+        std::fs::write(&to, "chevron seven")?;
+        assert_eq!(true, atom.plan()?.should_run);
+
+        assert_eq!(
+            Some(checksum.clone()),
+            find_checksum(
+                &format!("{}  other.txt\n{checksum} *gate.txt\n", "0".repeat(64)),
+                "https://sgc.mil/gate.txt?raw=1"
+            )
+        );
+        assert_eq!(
+            Some(checksum.clone()),
+            find_checksum(&format!("{checksum}\n"), "https://sgc.mil/gate.txt")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_downloads_again_once_expired() -> anyhow::Result<()> {
+        let url = serve("chevron seven locked");
+        let tmpdir = tempdir()?;
+        let to = tmpdir.path().join("gate.txt");
+
+        let mut atom = Download {
+            ttl: Some(Duration::from_secs(3600)),
+            ..Download::new(url, to.clone())
+        };
+        atom.execute()?;
+        assert_eq!(false, atom.plan()?.should_run);
+
+        atom.ttl = Some(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(true, atom.plan()?.should_run);
+
+        Ok(())
+    }
 }
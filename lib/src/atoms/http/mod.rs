@@ -1,6 +1,6 @@
 use super::Atom;
 
 mod download;
-pub use download::Download;
+pub use download::{Checksum, Download};
 
 pub trait HttpAtom: Atom {}
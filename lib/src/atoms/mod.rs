@@ -1,3 +1,4 @@
+pub mod checkpoint;
 pub mod command;
 pub mod directory;
 pub mod file;
@@ -5,6 +6,8 @@ pub mod git;
 pub mod http;
 pub mod plugin;
 
+pub use checkpoint::Checkpoint;
+
 pub enum SideEffect {}
 
 pub struct Outcome {
@@ -19,6 +22,12 @@ pub trait Atom: std::fmt::Display {
     // Apply new to old
     fn execute(&mut self) -> anyhow::Result<()>;
 
+    // Captures what `execute` is about to change, so atomic manifests can
+    // undo it. `None` for atoms that can't be undone, like running commands
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(None)
+    }
+
     // These methods allow for finalizers to query the outcome of the Atom.
     // We'll provide default implementations to allow Atoms to opt in to
     // the queries that make sense for them
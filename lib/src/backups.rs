@@ -0,0 +1,439 @@
+use crate::utilities::{symlink, write_private};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Retention of the backups of files changed by atoms
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackupsConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// Number of runs to keep backups of
+    #[serde(default = "default_keep_runs")]
+    pub keep_runs: usize,
+
+    /// Days to keep backups for, regardless of `keep_runs`
+    #[serde(default)]
+    pub keep_days: Option<u64>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_keep_runs() -> usize {
+    20
+}
+
+impl Default for BackupsConfig {
+    fn default() -> Self {
+        BackupsConfig {
+            enabled: default_enabled(),
+            keep_runs: default_keep_runs(),
+            keep_days: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Symlink,
+    Directory,
+}
+
+/// The state of a path before an atom changed it
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+
+    /// SHA-256 of the contents of a file, the name of its object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+/// Everything backed up during one run of comtrya
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Run {
+    pub id: String,
+    /// Seconds since the Unix epoch
+    pub started: u64,
+    pub entries: Vec<Entry>,
+}
+
+impl Run {
+    pub fn entry(&self, path: &Path) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+}
+
+/// Content addressed store of files, in `objects/` by their SHA-256, with
+/// an index per run in `runs/`
+pub struct BackupStore {
+    pub dir: PathBuf,
+    run: Run,
+}
+
+impl BackupStore {
+    /// The store in the platform's local data directory, if there is one
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs_next::data_local_dir().map(|dir| dir.join("comtrya").join("backups"))
+    }
+
+    /// Opens the store for a new run
+    pub fn open(dir: PathBuf) -> BackupStore {
+        let started = now();
+
+        BackupStore {
+            dir,
+            run: Run {
+                id: format!("{started}-{}", std::process::id()),
+                started,
+                entries: vec![],
+            },
+        }
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run.id
+    }
+
+    /// What was backed up during this run so far
+    pub fn current_run(&self) -> &Run {
+        &self.run
+    }
+
+    /// Keeps the current state of `path`, unless it was backed up during this
+    /// run already. Missing paths are ignored.
+    pub fn backup(&mut self, path: &Path) -> Result<()> {
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            return Ok(());
+        };
+
+        let path = std::path::absolute(path)?;
+
+        if self.run.entry(&path).is_some() {
+            return Ok(());
+        }
+
+        let entry = if metadata.is_symlink() {
+            Entry {
+                path: path.clone(),
+                kind: EntryKind::Symlink,
+                object: None,
+                link_target: Some(std::fs::read_link(&path)?),
+                mode: None,
+            }
+        } else if metadata.is_dir() {
+            Entry {
+                path: path.clone(),
+                kind: EntryKind::Directory,
+                object: None,
+                link_target: None,
+                mode: mode(&metadata),
+            }
+        } else {
+            let contents = std::fs::read(&path)
+                .with_context(|| format!("Failed to read {} to back it up", path.display()))?;
+            let object = sha256::digest(contents.as_slice());
+
+            let object_path = self.dir.join("objects").join(&object);
+            if !object_path.exists() {
+                write_private(&object_path, &contents)?;
+            }
+
+            Entry {
+                path: path.clone(),
+                kind: EntryKind::File,
+                object: Some(object),
+                link_target: None,
+                mode: mode(&metadata),
+            }
+        };
+
+        debug!("Backed up {} in run {}", path.display(), self.run.id);
+
+        self.run.entries.push(entry);
+        write_private(
+            &self.run_path(&self.run.id),
+            serde_yaml_ng::to_string(&self.run)?.as_bytes(),
+        )
+    }
+
+    /// Every run with backups, oldest first
+    pub fn runs(&self) -> Result<Vec<Run>> {
+        let dir = self.dir.join("runs");
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut runs = vec![];
+
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+
+            match std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|yaml| Ok(serde_yaml_ng::from_str::<Run>(&yaml)?))
+            {
+                Ok(run) => runs.push(run),
+                Err(err) => warn!("Skipping backup index {}: {:#}", path.display(), err),
+            }
+        }
+
+        runs.sort_by(|a, b| (a.started, &a.id).cmp(&(b.started, &b.id)));
+
+        Ok(runs)
+    }
+
+    /// A run by its id, or the most recent one for `latest`
+    pub fn run(&self, id: &str) -> Result<Run> {
+        let runs = self.runs()?;
+
+        match id {
+            "latest" => runs.into_iter().last(),
+            id => runs.into_iter().find(|run| run.id == id),
+        }
+        .ok_or_else(|| anyhow!("No backups of run '{id}'"))
+    }
+
+    /// The backed up contents of a file
+    pub fn contents(&self, entry: &Entry) -> Result<Vec<u8>> {
+        let object = entry
+            .object
+            .as_ref()
+            .ok_or_else(|| anyhow!("{} wasn't a file", entry.path.display()))?;
+
+        std::fs::read(self.dir.join("objects").join(object))
+            .with_context(|| format!("Backup of {} is missing", entry.path.display()))
+    }
+
+    /// Puts a path back into its backed up state, backing up what's there now
+    pub fn restore(&mut self, entry: &Entry) -> Result<()> {
+        self.backup(&entry.path)?;
+
+        if let Some(parent) = entry.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        match entry.kind {
+            EntryKind::File => {
+                let contents = self.contents(entry)?;
+                remove_link(&entry.path)?;
+                std::fs::write(&entry.path, contents)?;
+                set_mode(&entry.path, entry.mode)?;
+            }
+            EntryKind::Symlink => {
+                let link_target = entry
+                    .link_target
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Backup of {} has no target", entry.path.display()))?;
+
+                if std::fs::symlink_metadata(&entry.path).is_ok_and(|metadata| !metadata.is_dir()) {
+                    std::fs::remove_file(&entry.path)?;
+                }
+
+                symlink(link_target, &entry.path)?;
+            }
+            EntryKind::Directory => {
+                remove_link(&entry.path)?;
+                std::fs::create_dir_all(&entry.path)?;
+                set_mode(&entry.path, entry.mode)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes runs beyond the retention, and objects no run refers to
+    pub fn prune(&self, config: &BackupsConfig) -> Result<()> {
+        let runs = self.runs()?;
+        let cutoff = config
+            .keep_days
+            .map(|days| now().saturating_sub(days * 24 * 60 * 60));
+
+        let keep_from = runs.len().saturating_sub(config.keep_runs);
+        let mut referenced = BTreeSet::new();
+
+        for (index, run) in runs.iter().enumerate() {
+            let expired = index < keep_from || cutoff.is_some_and(|cutoff| run.started < cutoff);
+
+            if expired {
+                debug!("Removing backups of run {}", run.id);
+                std::fs::remove_file(self.run_path(&run.id))?;
+            } else {
+                referenced.extend(run.entries.iter().filter_map(|entry| entry.object.clone()));
+            }
+        }
+
+        let objects = self.dir.join("objects");
+        if objects.is_dir() {
+            for object in std::fs::read_dir(&objects)? {
+                let object = object?;
+
+                if !referenced.contains(object.file_name().to_string_lossy().as_ref()) {
+                    std::fs::remove_file(object.path())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_path(&self, id: &str) -> PathBuf {
+        self.dir.join("runs").join(format!("{id}.yaml"))
+    }
+}
+
+static STORE: Mutex<Option<BackupStore>> = Mutex::new(None);
+
+/// Makes atoms back up what they overwrite or remove into `store`
+pub fn init(store: BackupStore) {
+    *STORE.lock().unwrap_or_else(|err| err.into_inner()) = Some(store);
+}
+
+/// Backs up `path` before an atom changes it, if backups are enabled
+pub fn backup(path: &Path) -> Result<()> {
+    match STORE.lock().unwrap_or_else(|err| err.into_inner()).as_mut() {
+        Some(store) => store
+            .backup(path)
+            .with_context(|| format!("Failed to back up {}", path.display())),
+        None => Ok(()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn remove_link(path: &Path) -> Result<()> {
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink()) {
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(_: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &Path, _: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_backs_up_and_restores() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let dir = tempfile::tempdir()?;
+
+        let bashrc = dir.path().join(".bashrc");
+        let removed = dir.path().join(".profile");
+        std::fs::write(&bashrc, "export PS1='$ '")?;
+        std::fs::write(&removed, "umask 022")?;
+
+        let mut store = BackupStore::open(store_dir.path().to_path_buf());
+        store.backup(&bashrc)?;
+        std::fs::write(&bashrc, "export PS1='# '")?;
+        // Only the state before the run is kept
+        store.backup(&bashrc)?;
+        store.backup(&removed)?;
+        std::fs::remove_file(&removed)?;
+        store.backup(&dir.path().join("missing"))?;
+
+        let run = store.run("latest")?;
+        assert_eq!(store.run_id(), run.id);
+        assert_eq!(2, run.entries.len());
+        assert_eq!(
+            b"export PS1='$ '".to_vec(),
+            store.contents(run.entry(&bashrc).unwrap())?
+        );
+
+        let mut restore = BackupStore::open(store_dir.path().to_path_buf());
+        for entry in run.entries.iter() {
+            restore.restore(entry)?;
+        }
+
+        assert_eq!("export PS1='$ '", std::fs::read_to_string(&bashrc)?);
+        assert_eq!("umask 022", std::fs::read_to_string(&removed)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_prunes_old_runs() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let dir = tempfile::tempdir()?;
+
+        for (started, contents) in [(1, "Abydos"), (2, "Chulak"), (3, "Dakara")] {
+            let path = dir.path().join(contents);
+            std::fs::write(&path, contents)?;
+
+            let mut store = BackupStore::open(store_dir.path().to_path_buf());
+            store.run.id = format!("run-{started}");
+            store.run.started = started;
+            store.backup(&path)?;
+        }
+
+        let store = BackupStore::open(store_dir.path().to_path_buf());
+        store.prune(&BackupsConfig {
+            keep_runs: 2,
+            ..Default::default()
+        })?;
+
+        let runs: Vec<String> = store.runs()?.into_iter().map(|run| run.id).collect();
+        assert_eq!(vec!["run-2", "run-3"], runs);
+        assert_eq!(
+            2,
+            std::fs::read_dir(store_dir.path().join("objects"))?.count()
+        );
+
+        store.prune(&BackupsConfig {
+            keep_days: Some(1),
+            ..Default::default()
+        })?;
+        assert!(store.runs()?.is_empty());
+
+        Ok(())
+    }
+}
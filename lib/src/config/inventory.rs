@@ -0,0 +1,326 @@
+use super::matches_hostname;
+use anyhow::{anyhow, Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// An inventory maps hostnames, or groups of hosts, to variables,
+/// default labels and the manifests that should be applied to them.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Inventory {
+    #[serde(default)]
+    pub groups: IndexMap<String, HostGroup>,
+
+    /// Keyed by hostname or hostname glob pattern (e.g. `ws-*`)
+    #[serde(default)]
+    pub hosts: IndexMap<String, HostEntry>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HostGroup {
+    /// Hostname glob patterns that are members of this group
+    #[serde(default)]
+    pub hosts: Vec<String>,
+
+    /// Groups this group is composed of; their values are applied first
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub manifests: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HostEntry {
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub manifests: Vec<String>,
+}
+
+/// The result of resolving an inventory for a single hostname
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Host {
+    pub name: String,
+    pub groups: Vec<String>,
+    pub variables: BTreeMap<String, String>,
+    pub labels: Vec<String>,
+    pub manifests: Vec<String>,
+}
+
+impl Host {
+    fn merge(
+        &mut self,
+        variables: &BTreeMap<String, String>,
+        labels: &[String],
+        manifests: &[String],
+    ) {
+        self.variables
+            .extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        for label in labels {
+            if !self.labels.contains(label) {
+                self.labels.push(label.clone());
+            }
+        }
+
+        for manifest in manifests {
+            if !self.manifests.contains(manifest) {
+                self.manifests.push(manifest.clone());
+            }
+        }
+    }
+}
+
+impl Inventory {
+    pub fn load(path: &Path) -> Result<Inventory> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read inventory {}", path.display()))?;
+
+        serde_yaml_ng::from_str(&contents)
+            .with_context(|| format!("Unable to parse inventory {}", path.display()))
+    }
+
+    /// Resolves the groups, variables, labels and manifests of `hostname`.
+    ///
+    /// Values are applied from lowest to highest precedence:
+    /// - groups, in the order they are declared in the inventory, with
+    ///   composed groups applied before the group that includes them
+    /// - host entries whose pattern matches, in declaration order, with
+    ///   an exact hostname match applied last
+    pub fn resolve(&self, hostname: &str) -> Result<Host> {
+        let mut host_entries: Vec<&HostEntry> = vec![];
+        let mut exact_entry: Option<&HostEntry> = None;
+
+        for (pattern, entry) in self.hosts.iter() {
+            if pattern == hostname {
+                exact_entry = Some(entry);
+            } else if matches_hostname(pattern, hostname)? {
+                host_entries.push(entry);
+            }
+        }
+        host_entries.extend(exact_entry);
+
+        let mut member_of: Vec<&str> = vec![];
+
+        for (name, group) in self.groups.iter() {
+            for pattern in group.hosts.iter() {
+                if matches_hostname(pattern, hostname)? {
+                    member_of.push(name);
+                    break;
+                }
+            }
+        }
+
+        for entry in host_entries.iter() {
+            member_of.extend(entry.groups.iter().map(String::as_str));
+        }
+
+        let mut ordered_groups: Vec<String> = vec![];
+        for name in member_of {
+            self.expand_group(name, &mut vec![], &mut ordered_groups)?;
+        }
+
+        // Groups are applied in declaration order, so that composition is the
+        // only thing that can reorder them
+        ordered_groups.sort_by_key(|name| self.groups.get_index_of(name));
+        let ordered_groups = self.parents_first(ordered_groups);
+
+        let mut host = Host {
+            name: hostname.to_string(),
+            ..Default::default()
+        };
+
+        for name in ordered_groups.iter() {
+            // Existence was checked when expanding the group
+            let group = &self.groups[name.as_str()];
+            host.merge(&group.variables, &group.labels, &group.manifests);
+        }
+
+        for entry in host_entries {
+            host.merge(&entry.variables, &entry.labels, &entry.manifests);
+        }
+
+        host.groups = ordered_groups;
+
+        Ok(host)
+    }
+
+    fn expand_group(
+        &self,
+        name: &str,
+        visiting: &mut Vec<String>,
+        ordered_groups: &mut Vec<String>,
+    ) -> Result<()> {
+        if visiting.iter().any(|visited| visited == name) {
+            return Err(anyhow!(
+                "Inventory group '{name}' is composed of itself: {} -> {name}",
+                visiting.join(" -> ")
+            ));
+        }
+
+        let group = self
+            .groups
+            .get(name)
+            .ok_or_else(|| anyhow!("Inventory references unknown group '{name}'"))?;
+
+        visiting.push(name.to_string());
+        for parent in group.groups.iter() {
+            self.expand_group(parent, visiting, ordered_groups)?;
+        }
+        visiting.pop();
+
+        if !ordered_groups.iter().any(|group| group == name) {
+            ordered_groups.push(name.to_string());
+        }
+
+        Ok(())
+    }
+
+    fn parents_first(&self, groups: Vec<String>) -> Vec<String> {
+        let mut ordered: Vec<String> = vec![];
+
+        fn visit(inventory: &Inventory, name: &str, ordered: &mut Vec<String>) {
+            if ordered.iter().any(|group| group == name) {
+                return;
+            }
+
+            if let Some(group) = inventory.groups.get(name) {
+                for parent in group.groups.iter() {
+                    visit(inventory, parent, ordered);
+                }
+            }
+
+            ordered.push(name.to_string());
+        }
+
+        for name in groups.iter() {
+            visit(self, name, &mut ordered);
+        }
+
+        ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn inventory() -> Inventory {
+        serde_yaml_ng::from_str(
+            r#"
+groups:
+  base:
+    variables:
+      shell: bash
+      editor: nano
+    labels:
+      - base
+  workstations:
+    hosts:
+      - "ws-*"
+    groups:
+      - base
+    variables:
+      editor: vim
+    labels:
+      - desktop
+    manifests:
+      - dev
+  gpu:
+    variables:
+      driver: nvidia
+    manifests:
+      - gpu
+hosts:
+  "ws-1*":
+    variables:
+      shell: zsh
+  ws-10:
+    groups:
+      - gpu
+    variables:
+      shell: fish
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_resolves_group_membership_by_pattern() {
+        let host = inventory().resolve("ws-02").unwrap();
+
+        assert_eq!(vec!["base", "workstations"], host.groups);
+        assert_eq!("vim", host.variables["editor"]);
+        assert_eq!("bash", host.variables["shell"]);
+        assert_eq!(vec!["base", "desktop"], host.labels);
+        assert_eq!(vec!["dev"], host.manifests);
+    }
+
+    #[test]
+    fn it_applies_host_entries_last() {
+        let host = inventory().resolve("ws-10").unwrap();
+
+        assert_eq!(vec!["base", "workstations", "gpu"], host.groups);
+        assert_eq!("fish", host.variables["shell"]);
+        assert_eq!("nvidia", host.variables["driver"]);
+        assert_eq!(vec!["dev", "gpu"], host.manifests);
+
+        let host = inventory().resolve("ws-11").unwrap();
+        assert_eq!("zsh", host.variables["shell"]);
+    }
+
+    #[test]
+    fn it_resolves_unknown_hosts_to_nothing() {
+        let host = inventory().resolve("server").unwrap();
+
+        assert_eq!(
+            Host {
+                name: String::from("server"),
+                ..Default::default()
+            },
+            host
+        );
+    }
+
+    #[test]
+    fn it_rejects_cycles_and_unknown_groups() {
+        let inventory: Inventory = serde_yaml_ng::from_str(
+            r#"
+groups:
+  a:
+    hosts: ["*"]
+    groups: [b]
+  b:
+    groups: [a]
+"#,
+        )
+        .unwrap();
+        assert!(inventory.resolve("any").is_err());
+
+        let inventory: Inventory = serde_yaml_ng::from_str(
+            r#"
+hosts:
+  any:
+    groups: [missing]
+"#,
+        )
+        .unwrap();
+        assert!(inventory.resolve("any").is_err());
+    }
+}
@@ -1,6 +1,21 @@
+use crate::backups::BackupsConfig;
+use crate::contexts::facts::Fact;
 use crate::contexts::privilege::Privilege;
+use crate::contexts::prompts::Prompt;
+use crate::contexts::variable_include::VariableInclude;
+use crate::http::HttpConfig;
+use anyhow::{Context, Result};
+use gethostname::gethostname;
+use globset::Glob;
+use indexmap::IndexMap;
+use inventory::{Host, Inventory};
+use profile::Profile;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub mod inventory;
+pub mod profile;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Config {
@@ -11,11 +26,106 @@ pub struct Config {
     pub variables: BTreeMap<String, String>,
 
     #[serde(default)]
-    pub include_variables: Option<Vec<String>>,
+    pub include_variables: Option<Vec<VariableInclude>>,
+
+    /// Seconds cached results of remote variable includes are used for,
+    /// an hour when not set
+    #[serde(default)]
+    pub include_variables_ttl: Option<u64>,
+
+    /// Ignore cached variable includes that are still fresh
+    #[serde(skip)]
+    pub refresh_includes: bool,
 
     #[serde(default)]
     pub disable_update_check: bool,
 
     #[serde(default)]
     pub privilege: Privilege,
+
+    #[serde(default)]
+    pub facts: BTreeMap<String, Fact>,
+
+    /// Path to a host inventory, relative to the directory of `Comtrya.yaml`
+    #[serde(default)]
+    pub inventory: Option<String>,
+
+    #[serde(default)]
+    pub profiles: IndexMap<String, Profile>,
+
+    /// Values asked for once per machine, in this order
+    #[serde(default)]
+    pub prompts: IndexMap<String, Prompt>,
+
+    /// Directory of shared templates, relative to the manifest path.
+    /// Defaults to `templates`
+    #[serde(default)]
+    pub templates_dir: Option<String>,
+
+    /// Age identity files and SSH private keys used by `comtrya secrets`
+    #[serde(default)]
+    pub identities: Vec<String>,
+
+    /// Age recipients, SSH public keys or files listing them, that
+    /// `comtrya secrets` encrypts to
+    #[serde(default)]
+    pub recipients: Vec<String>,
+
+    /// Path to an age encrypted YAML map of secrets, relative to the
+    /// directory of `Comtrya.yaml`
+    #[serde(default)]
+    pub secrets_file: Option<String>,
+
+    /// Retention of backups of files that are overwritten or removed
+    #[serde(default)]
+    pub backups: BackupsConfig,
+
+    /// Timeouts, retries, proxy and credentials of HTTP requests
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// The inventory entry resolved for the current host
+    #[serde(skip)]
+    pub host: Option<Host>,
+}
+
+impl Config {
+    /// Loads the configured inventory and resolves it for the current hostname.
+    /// Host variables take precedence over `variables`.
+    pub fn apply_inventory(&mut self, base_dir: &Path) -> Result<()> {
+        let hostname = gethostname().to_string_lossy().to_string();
+
+        self.apply_inventory_for(base_dir, &hostname)
+    }
+
+    pub fn apply_inventory_for(&mut self, base_dir: &Path, hostname: &str) -> Result<()> {
+        let Some(inventory) = &self.inventory else {
+            return Ok(());
+        };
+
+        let host = Inventory::load(&base_dir.join(inventory))?.resolve(hostname)?;
+
+        self.variables.extend(host.variables.clone());
+        self.host = Some(host);
+
+        Ok(())
+    }
+}
+
+impl Config {
+    pub fn include_variables_ttl(&self) -> u64 {
+        self.include_variables_ttl.unwrap_or(3600)
+    }
+
+    /// The directory of shared templates for manifests at `manifest_path`
+    pub fn templates_path(&self, manifest_path: &Path) -> PathBuf {
+        manifest_path.join(self.templates_dir.as_deref().unwrap_or("templates"))
+    }
+}
+
+pub(crate) fn matches_hostname(pattern: &str, hostname: &str) -> Result<bool> {
+    let glob =
+        Glob::new(pattern).with_context(|| format!("Invalid hostname pattern '{pattern}'"))?;
+
+    Ok(glob.compile_matcher().is_match(hostname))
 }
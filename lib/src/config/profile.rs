@@ -0,0 +1,203 @@
+use super::{matches_hostname, Config};
+use crate::contexts::{to_rhai, Contexts};
+use anyhow::{anyhow, Result};
+use rhai::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::{debug, warn};
+
+/// A named bundle of manifest selections, label selectors, variable
+/// overrides and a failure policy.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub manifests: Vec<String>,
+
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+
+    /// Hostname glob patterns that select this profile when none is requested
+    #[serde(default)]
+    pub hosts: Vec<String>,
+
+    /// Condition that selects this profile when none is requested
+    #[serde(default)]
+    pub r#where: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Stop applying manifests once one has failed
+    #[default]
+    Stop,
+
+    /// Keep applying the remaining manifests
+    Continue,
+}
+
+impl Profile {
+    fn is_default_for(&self, hostname: &str, engine: &Engine, contexts: &Contexts) -> Result<bool> {
+        for pattern in self.hosts.iter() {
+            if matches_hostname(pattern, hostname)? {
+                return Ok(true);
+            }
+        }
+
+        let Some(condition) = &self.r#where else {
+            return Ok(false);
+        };
+
+        let mut scope = to_rhai(contexts);
+        match engine.eval_with_scope::<bool>(&mut scope, condition) {
+            Ok(result) => {
+                debug!(
+                    "Result of profile condition '{}' -> '{}'",
+                    condition, result
+                );
+                Ok(result)
+            }
+            Err(err) => {
+                warn!("Profile condition '{}' failed: {}", condition, err);
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Selects the requested profile or, if none was requested, the first
+    /// profile whose `hosts` or `where` match this machine.
+    pub fn select_profile(
+        &self,
+        requested: Option<&str>,
+        hostname: &str,
+        contexts: &Contexts,
+    ) -> Result<Option<(String, Profile)>> {
+        if let Some(name) = requested {
+            return match self.profiles.get(name) {
+                Some(profile) => Ok(Some((name.to_string(), profile.clone()))),
+                None => Err(anyhow!("Profile '{name}' is not defined in Comtrya.yaml")),
+            };
+        }
+
+        let engine = Engine::new();
+
+        for (name, profile) in self.profiles.iter() {
+            if profile.is_default_for(hostname, &engine, contexts)? {
+                return Ok(Some((name.clone(), profile.clone())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Overlays the variables of `profile` onto the config and the
+    /// already built `variables` context.
+    pub fn apply_profile(&mut self, profile: &Profile, contexts: &mut Contexts) {
+        let variables = contexts.entry(String::from("variables")).or_default();
+
+        for (key, value) in profile.variables.iter() {
+            self.variables.insert(key.clone(), value.clone());
+            variables.insert(key.clone(), value.clone().into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+
+    fn config() -> Config {
+        serde_yaml_ng::from_str(
+            r#"
+variables:
+  editor: nano
+profiles:
+  server:
+    where: 'os.name == "plan9"'
+  work-laptop:
+    hosts: ["laptop-*"]
+    manifests: [dev, vpn]
+    labels: [work]
+    on_failure: continue
+    variables:
+      editor: vim
+  home:
+    where: 'user.username == "jack"'
+"#,
+        )
+        .unwrap()
+    }
+
+    fn contexts(username: &str) -> Contexts {
+        let mut user = BTreeMap::new();
+        user.insert(String::from("username"), Value::from(username));
+
+        let mut os = BTreeMap::new();
+        os.insert(String::from("name"), Value::from("linux"));
+
+        let mut contexts = Contexts::new();
+        contexts.insert(String::from("user"), user);
+        contexts.insert(String::from("os"), os);
+        contexts
+    }
+
+    #[test]
+    fn it_selects_the_requested_profile() {
+        let config = config();
+
+        let (name, profile) = config
+            .select_profile(Some("work-laptop"), "desktop", &contexts("sam"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!("work-laptop", name);
+        assert_eq!(vec!["dev", "vpn"], profile.manifests);
+        assert_eq!(FailurePolicy::Continue, profile.on_failure);
+
+        assert!(config
+            .select_profile(Some("missing"), "desktop", &contexts("sam"))
+            .is_err());
+    }
+
+    #[test]
+    fn it_selects_a_default_profile() {
+        let config = config();
+
+        let selected = config
+            .select_profile(None, "laptop-01", &contexts("sam"))
+            .unwrap();
+        assert_eq!("work-laptop", selected.unwrap().0);
+
+        let selected = config
+            .select_profile(None, "desktop", &contexts("jack"))
+            .unwrap();
+        assert_eq!("home", selected.unwrap().0);
+
+        let selected = config
+            .select_profile(None, "desktop", &contexts("sam"))
+            .unwrap();
+        assert!(selected.is_none());
+    }
+
+    #[test]
+    fn it_overlays_profile_variables() {
+        let mut config = config();
+        let mut contexts = contexts("sam");
+        let profile = config.profiles["work-laptop"].clone();
+
+        config.apply_profile(&profile, &mut contexts);
+
+        assert_eq!("vim", config.variables["editor"]);
+        assert_eq!("vim", contexts["variables"]["editor"].to_string());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::config::Config;
+use crate::contexts::{Context, ContextProvider};
+use crate::values::Value;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use tracing::warn;
+
+/// A fact is read from the output of a command or the contents of a file,
+/// optionally narrowed down by a regex and coerced to a type.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Fact {
+    pub command: Option<String>,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    pub file: Option<String>,
+
+    /// The first capture group is used if there is one, otherwise the whole match
+    pub regex: Option<String>,
+
+    #[serde(default, rename = "type")]
+    pub kind: FactType,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactType {
+    #[default]
+    String,
+    Integer,
+    Float,
+    Boolean,
+    /// Every line, or every regex match
+    List,
+}
+
+impl Fact {
+    fn source(&self) -> Result<String> {
+        match (&self.command, &self.file) {
+            (Some(command), None) => {
+                let output = Command::new(command).args(&self.args).output()?;
+
+                if !output.status.success() {
+                    return Err(anyhow!("Command {command} exited with {}", output.status));
+                }
+
+                Ok(String::from_utf8(output.stdout)?)
+            }
+            (None, Some(file)) => Ok(std::fs::read_to_string(file)?),
+            _ => Err(anyhow!("A fact needs exactly one of 'command' or 'file'")),
+        }
+    }
+
+    pub fn resolve(&self) -> Result<Value> {
+        let source = self.source()?;
+
+        let mut matches: Vec<String> = match &self.regex {
+            Some(regex) => {
+                let regex = Regex::new(regex)?;
+
+                regex
+                    .captures_iter(&source)
+                    .filter_map(|captures| captures.get(1).or_else(|| captures.get(0)))
+                    .map(|m| m.as_str().to_string())
+                    .collect()
+            }
+            None if self.kind == FactType::List => source
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+            None => vec![source.trim().to_string()],
+        };
+
+        if self.kind == FactType::List {
+            return Ok(matches.into());
+        }
+
+        if matches.is_empty() {
+            return Err(anyhow!("Regex didn't match"));
+        }
+
+        let value = matches.swap_remove(0);
+
+        Ok(match self.kind {
+            FactType::String | FactType::List => value.into(),
+            FactType::Integer => value.parse::<i64>()?.into(),
+            FactType::Float => value.parse::<f64>()?.into(),
+            FactType::Boolean => match value.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => true.into(),
+                "false" | "no" | "off" | "0" | "" => false.into(),
+                _ => return Err(anyhow!("Cannot coerce '{value}' to a boolean")),
+            },
+        })
+    }
+}
+
+pub struct FactsContextProvider<'a> {
+    pub config: &'a Config,
+}
+
+impl<'a> ContextProvider for FactsContextProvider<'a> {
+    fn get_prefix(&self) -> String {
+        String::from("facts")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<Context>> {
+        let mut contexts = vec![];
+
+        for (name, fact) in self.config.facts.iter() {
+            match fact.resolve() {
+                Ok(value) => contexts.push(Context::KeyValueContext(name.clone(), value)),
+                Err(err) => warn!("Failed to resolve fact {}: {}", name, err),
+            }
+        }
+
+        Ok(contexts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_reads_files_with_a_regex() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let resolv = dir.path().join("resolv.conf");
+        std::fs::write(
+            &resolv,
+            "search corp.example.com\nnameserver 10.0.0.1\nnameserver 10.0.0.2\n",
+        )?;
+
+        let fact = Fact {
+            file: Some(resolv.display().to_string()),
+            regex: Some(String::from(r"search (\S+)")),
+            ..Default::default()
+        };
+        assert_eq!("corp.example.com", fact.resolve()?.to_string());
+
+        let fact = Fact {
+            file: Some(resolv.display().to_string()),
+            regex: Some(String::from(r"nameserver (\S+)")),
+            kind: FactType::List,
+            ..Default::default()
+        };
+        assert_eq!("10.0.0.1,10.0.0.2", fact.resolve()?.to_string());
+
+        let fact = Fact {
+            file: Some(resolv.display().to_string()),
+            regex: Some(String::from(r"domain (\S+)")),
+            ..Default::default()
+        };
+        assert!(fact.resolve().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_coerces_command_output() -> anyhow::Result<()> {
+        let fact = Fact {
+            command: Some(String::from("echo")),
+            args: vec![String::from("42")],
+            kind: FactType::Integer,
+            ..Default::default()
+        };
+        assert_eq!(Value::from(42i64), fact.resolve()?);
+
+        let fact = Fact {
+            command: Some(String::from("echo")),
+            args: vec![String::from("yes")],
+            kind: FactType::Boolean,
+            ..Default::default()
+        };
+        assert_eq!(Value::from(true), fact.resolve()?);
+
+        let fact = Fact {
+            command: Some(String::from("echo")),
+            args: vec![String::from("many")],
+            kind: FactType::Float,
+            ..Default::default()
+        };
+        assert!(fact.resolve().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_facts_that_fail() -> anyhow::Result<()> {
+        let config: Config = serde_yaml_ng::from_str(
+            r#"
+facts:
+  broken:
+    file: /does/not/exist
+  ambiguous:
+    command: echo
+    file: /etc/hosts
+"#,
+        )?;
+
+        let contexts = FactsContextProvider { config: &config }.get_contexts()?;
+        assert!(contexts.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use crate::contexts::{Context, ContextProvider};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Reads the CPU, memory, disks and virtualization of the machine.
+/// On Linux, everything but the architecture comes from `/proc` and
+/// `/sys` below `root`, which allows testing against fixture trees.
+pub struct HardwareContextProvider {
+    pub root: PathBuf,
+}
+
+const HYPERVISORS: [(&str, &str); 7] = [
+    ("qemu", "qemu"),
+    ("kvm", "kvm"),
+    ("vmware", "vmware"),
+    ("virtualbox", "virtualbox"),
+    ("innotek", "virtualbox"),
+    ("xen", "xen"),
+    ("microsoft corporation", "hyperv"),
+];
+
+impl ContextProvider for HardwareContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("hardware")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<Context>> {
+        let cpuinfo = self.read("proc/cpuinfo").unwrap_or_default();
+
+        let cpu_count = cpuinfo
+            .lines()
+            .filter(|line| line.starts_with("processor"))
+            .count();
+        let cpu_count = match cpu_count {
+            0 => std::thread::available_parallelism()
+                .map(|count| count.get())
+                .unwrap_or(1),
+            count => count,
+        };
+
+        let cpu_model = cpuinfo
+            .lines()
+            .find_map(|line| {
+                line.strip_prefix("model name")
+                    .and_then(|rest| rest.split_once(':'))
+                    .map(|(_, model)| model.trim().to_string())
+            })
+            .unwrap_or_else(|| String::from("unknown"));
+
+        let memory_total = self
+            .read("proc/meminfo")
+            .and_then(|meminfo| {
+                meminfo.lines().find_map(|line| {
+                    line.strip_prefix("MemTotal:").and_then(|rest| {
+                        rest.trim()
+                            .trim_end_matches("kB")
+                            .trim()
+                            .parse::<u64>()
+                            .ok()
+                    })
+                })
+            })
+            .map(|kilobytes| kilobytes * 1024)
+            .unwrap_or(0);
+
+        let container = self.container();
+        let hypervisor = self.hypervisor(&cpuinfo);
+        let wsl = self.is_wsl();
+
+        let virtualization = if container.is_some() {
+            "container"
+        } else if wsl {
+            "wsl"
+        } else if hypervisor.is_some() {
+            "vm"
+        } else {
+            "none"
+        };
+
+        Ok(vec![
+            Context::KeyValueContext(String::from("arch"), std::env::consts::ARCH.into()),
+            Context::KeyValueContext(String::from("cpu_count"), (cpu_count as u64).into()),
+            Context::KeyValueContext(String::from("cpu_model"), cpu_model.into()),
+            Context::KeyValueContext(String::from("memory_total"), memory_total.into()),
+            Context::ListContext(
+                String::from("disks"),
+                self.disks().into_iter().map(Into::into).collect(),
+            ),
+            Context::KeyValueContext(String::from("virtualization"), virtualization.into()),
+            Context::KeyValueContext(
+                String::from("container"),
+                container
+                    .clone()
+                    .unwrap_or_else(|| String::from("none"))
+                    .into(),
+            ),
+            Context::KeyValueContext(
+                String::from("hypervisor"),
+                hypervisor
+                    .clone()
+                    .unwrap_or_else(|| String::from("none"))
+                    .into(),
+            ),
+            Context::KeyValueContext(String::from("is_container"), container.is_some().into()),
+            Context::KeyValueContext(String::from("is_vm"), hypervisor.is_some().into()),
+            Context::KeyValueContext(String::from("is_wsl"), wsl.into()),
+        ])
+    }
+}
+
+impl HardwareContextProvider {
+    fn read(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(self.root.join(path)).ok()
+    }
+
+    /// Block devices, without loop, ram and device mapper devices
+    fn disks(&self) -> Vec<String> {
+        let mut disks: Vec<String> = std::fs::read_dir(self.root.join("sys/block"))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| entry.file_name().to_str().map(String::from))
+                    .filter(|name| {
+                        !["loop", "ram", "dm-", "zram"]
+                            .iter()
+                            .any(|prefix| name.starts_with(prefix))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        disks.sort();
+        disks
+    }
+
+    fn container(&self) -> Option<String> {
+        if self.root.join(".dockerenv").exists() {
+            return Some(String::from("docker"));
+        }
+
+        if self.root.join("run/.containerenv").exists() {
+            return Some(String::from("podman"));
+        }
+
+        if let Some(environ) = self.read("proc/1/environ") {
+            if let Some(container) = environ
+                .split('\0')
+                .find_map(|variable| variable.strip_prefix("container="))
+            {
+                return Some(container.to_string());
+            }
+        }
+
+        let cgroup = self.read("proc/1/cgroup").unwrap_or_default();
+        ["docker", "kubepods", "lxc", "containerd"]
+            .iter()
+            .find(|runtime| cgroup.contains(*runtime))
+            .map(|runtime| runtime.to_string())
+    }
+
+    fn hypervisor(&self, cpuinfo: &str) -> Option<String> {
+        let dmi = [
+            "sys/class/dmi/id/sys_vendor",
+            "sys/class/dmi/id/product_name",
+        ]
+        .iter()
+        .filter_map(|path| self.read(path))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+        if let Some((_, hypervisor)) = HYPERVISORS.iter().find(|(needle, _)| dmi.contains(needle)) {
+            return Some(hypervisor.to_string());
+        }
+
+        let hypervisor_flag = cpuinfo
+            .lines()
+            .filter(|line| line.starts_with("flags"))
+            .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"));
+
+        hypervisor_flag.then(|| String::from("unknown"))
+    }
+
+    fn is_wsl(&self) -> bool {
+        self.read("proc/sys/kernel/osrelease")
+            .map(|release| release.to_lowercase().contains("microsoft"))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contexts::fixtures::{values, write};
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    fn hardware(root: &Path) -> BTreeMap<String, Value> {
+        values(&HardwareContextProvider {
+            root: root.to_path_buf(),
+        })
+    }
+
+    #[test]
+    fn it_reads_a_virtual_machine() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            "proc/cpuinfo",
+            "processor\t: 0\nmodel name\t: AMD EPYC 7763\nflags\t\t: fpu hypervisor\n\nprocessor\t: 1\nmodel name\t: AMD EPYC 7763\n",
+        );
+        write(
+            root.path(),
+            "proc/meminfo",
+            "MemTotal:        2048 kB\nMemFree: 1 kB\n",
+        );
+        write(root.path(), "sys/class/dmi/id/sys_vendor", "QEMU\n");
+        write(root.path(), "sys/block/vda/size", "0");
+        write(root.path(), "sys/block/loop0/size", "0");
+
+        let contexts = hardware(root.path());
+
+        assert_eq!("2", contexts["cpu_count"].to_string());
+        assert_eq!("AMD EPYC 7763", contexts["cpu_model"].to_string());
+        assert_eq!("2097152", contexts["memory_total"].to_string());
+        assert_eq!("vda", contexts["disks"].to_string());
+        assert_eq!("vm", contexts["virtualization"].to_string());
+        assert_eq!("qemu", contexts["hypervisor"].to_string());
+        assert_eq!("none", contexts["container"].to_string());
+        assert_eq!("1", contexts["is_vm"].to_string());
+    }
+
+    #[test]
+    fn it_reads_containers_and_wsl() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            "proc/1/cgroup",
+            "0::/kubepods/besteffort/pod1\n",
+        );
+
+        let contexts = hardware(root.path());
+        assert_eq!("container", contexts["virtualization"].to_string());
+        assert_eq!("kubepods", contexts["container"].to_string());
+        assert_eq!("1", contexts["is_container"].to_string());
+
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            "proc/sys/kernel/osrelease",
+            "5.15.90.1-microsoft-standard-WSL2\n",
+        );
+
+        let contexts = hardware(root.path());
+        assert_eq!("wsl", contexts["virtualization"].to_string());
+        assert_eq!("1", contexts["is_wsl"].to_string());
+        assert_eq!("0", contexts["is_vm"].to_string());
+    }
+
+    #[test]
+    fn it_falls_back_without_proc() {
+        let root = tempfile::tempdir().unwrap();
+
+        let contexts = hardware(root.path());
+
+        assert_eq!(std::env::consts::ARCH, contexts["arch"].to_string());
+        assert_eq!("unknown", contexts["cpu_model"].to_string());
+        assert_eq!("none", contexts["virtualization"].to_string());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::config::Config;
+use crate::contexts::{Context, ContextProvider};
+use anyhow::Result;
+
+const BUILT_IN: [&str; 4] = ["name", "groups", "labels", "manifests"];
+
+pub struct HostContextProvider<'a> {
+    pub config: &'a Config,
+}
+
+impl<'a> ContextProvider for HostContextProvider<'a> {
+    fn get_prefix(&self) -> String {
+        String::from("host")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<super::Context>> {
+        let Some(host) = &self.config.host else {
+            return Ok(vec![]);
+        };
+
+        // Variables of the host, unless they're named like one of the keys below
+        let variables = host
+            .variables
+            .iter()
+            .filter(|(key, _)| !BUILT_IN.contains(&key.as_str()))
+            .map(|(key, value)| Context::KeyValueContext(key.clone(), value.clone().into()));
+
+        let contexts = vec![
+            Context::KeyValueContext(String::from("name"), host.name.clone().into()),
+            Context::ListContext(
+                String::from("groups"),
+                host.groups.iter().cloned().map(Into::into).collect(),
+            ),
+            Context::ListContext(
+                String::from("labels"),
+                host.labels.iter().cloned().map(Into::into).collect(),
+            ),
+            Context::ListContext(
+                String::from("manifests"),
+                host.manifests.iter().cloned().map(Into::into).collect(),
+            ),
+        ];
+
+        Ok(variables.chain(contexts).collect())
+    }
+}
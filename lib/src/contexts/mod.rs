@@ -2,6 +2,7 @@ use anyhow::Result;
 use rhai::Scope;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 use tracing::{instrument, trace, warn};
 use user::UserContextProvider;
 
@@ -9,15 +10,29 @@ use crate::contexts::privilege::PrivilegeContextProvider;
 use crate::{
     config::Config,
     contexts::{
-        env::EnvContextProvider, os::OSContextProvider,
+        env::EnvContextProvider, facts::FactsContextProvider, hardware::HardwareContextProvider,
+        host::HostContextProvider, network::NetworkContextProvider, os::OSContextProvider,
+        prompts::PromptsContextProvider, secrets::SecretsContextProvider,
         variable_include::VariableIncludeContextProvider, variables::VariablesContextProvider,
     },
     values::Value,
 };
 
 pub mod env;
+/// Facts context provider: custom facts declared in `Comtrya.yaml`
+pub mod facts;
+/// Hardware context provider: CPU, memory, disks and virtualization
+pub mod hardware;
+/// Host context provider: the inventory entry resolved for this machine
+pub mod host;
+/// Network context provider: interfaces, routes and DNS settings
+pub mod network;
 pub mod os;
 pub mod privilege;
+/// Prompts context provider: stored answers to prompts
+pub mod prompts;
+/// Secrets context provider: encrypted variables and the secrets file
+pub mod secrets;
 /// User context provider: understands the user running the command
 pub mod user;
 pub mod variable_include;
@@ -43,10 +58,20 @@ pub fn build_contexts(config: &Config) -> Contexts {
     let context_providers: Vec<Box<dyn ContextProvider>> = vec![
         Box::new(UserContextProvider {}),
         Box::new(OSContextProvider {}),
+        Box::new(HardwareContextProvider {
+            root: PathBuf::from("/"),
+        }),
+        Box::new(NetworkContextProvider {
+            root: PathBuf::from("/"),
+        }),
+        Box::new(HostContextProvider { config }),
         Box::new(EnvContextProvider {}),
         Box::new(VariablesContextProvider { config }),
+        Box::new(SecretsContextProvider { config }),
         Box::new(VariableIncludeContextProvider { config }),
         Box::new(PrivilegeContextProvider { config }),
+        Box::new(FactsContextProvider { config }),
+        Box::new(PromptsContextProvider {}),
     ];
 
     context_providers.iter().for_each(|provider| {
@@ -65,6 +90,9 @@ pub fn build_contexts(config: &Config) -> Contexts {
             .unwrap_or_default()
             .iter()
             .for_each(|context| match context {
+                Context::KeyValueContext(k, v) if provider.get_prefix() == "secrets" => {
+                    values.insert(k.clone(), v.clone());
+                }
                 Context::KeyValueContext(k, v) => {
                     trace!(
                         context = provider.get_prefix().as_str(),
@@ -110,7 +138,10 @@ pub fn to_rhai(context: &Contexts) -> rhai::Scope<'_> {
             }
         };
 
-        trace!("Add dynamic constant '{}' -> {}", &m, &dynamic);
+        match m.as_str() {
+            "secrets" => trace!("Add dynamic constant '{}'", &m),
+            _ => trace!("Add dynamic constant '{}' -> {}", &m, &dynamic),
+        }
 
         scope.push_constant(m.clone(), dynamic);
     });
@@ -118,6 +149,35 @@ pub fn to_rhai(context: &Contexts) -> rhai::Scope<'_> {
     scope
 }
 
+/// Fake filesystem roots for providers that read from `/proc` and `/sys`
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{Context, ContextProvider};
+    use crate::values::Value;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    /// Writes `contents` to `path` within the fake `root`
+    pub fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// The values of `provider` by key
+    pub fn values(provider: &dyn ContextProvider) -> BTreeMap<String, Value> {
+        provider
+            .get_contexts()
+            .unwrap()
+            .into_iter()
+            .map(|context| match context {
+                Context::KeyValueContext(key, value) => (key, value),
+                Context::ListContext(key, values) => (key, values.into()),
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -213,4 +273,43 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn host_context_resolves_from_inventory() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("inventory.yaml"),
+            r#"
+groups:
+  workstations:
+    hosts: ["ws-*"]
+    variables:
+      ship_name: Daedalus
+    labels: [desktop]
+"#,
+        )?;
+
+        let mut variables = BTreeMap::new();
+        variables.insert("ship_name".to_string(), "Prometheus".to_string());
+
+        let mut config = Config {
+            variables,
+            inventory: Some(String::from("inventory.yaml")),
+            ..Default::default()
+        };
+        config.apply_inventory_for(dir.path(), "ws-01")?;
+
+        let contexts = build_contexts(&config);
+
+        let host = contexts.get("host").unwrap();
+        assert_eq!(host.get("name").unwrap().to_string(), "ws-01");
+        assert_eq!(host.get("groups").unwrap().to_string(), "workstations");
+        assert_eq!(host.get("labels").unwrap().to_string(), "desktop");
+        assert_eq!(host.get("ship_name").unwrap().to_string(), "Daedalus");
+
+        let variables = contexts.get("variables").unwrap();
+        assert_eq!(variables.get("ship_name").unwrap().to_string(), "Daedalus");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,358 @@
+use crate::contexts::{Context, ContextProvider};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+
+/// Reads interfaces, addresses, the default route and DNS settings.
+/// On Linux, everything comes from `/proc/net` and `/etc/resolv.conf`
+/// below `root`, which allows testing against fixture files.
+pub struct NetworkContextProvider {
+    pub root: PathBuf,
+}
+
+struct Route {
+    interface: String,
+    destination: Ipv4Addr,
+    gateway: Ipv4Addr,
+    mask: Ipv4Addr,
+    metric: u32,
+}
+
+struct Ipv6Address {
+    interface: String,
+    address: Ipv6Addr,
+    global: bool,
+}
+
+impl ContextProvider for NetworkContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("network")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<Context>> {
+        let interfaces = self.interfaces();
+        let routes = self.routes();
+        let ipv4 = self.ipv4_addresses(&routes);
+        let ipv6 = self.ipv6_addresses();
+
+        let default_route = routes
+            .iter()
+            .filter(|route| route.destination.is_unspecified() && route.mask.is_unspecified())
+            .min_by_key(|route| route.metric);
+
+        let default_interface = default_route.map(|route| route.interface.clone());
+
+        let primary_ipv4 = default_interface
+            .as_ref()
+            .and_then(|interface| ipv4.get(interface))
+            .and_then(|addresses| addresses.first())
+            .map(ToString::to_string);
+
+        let primary_ipv6 = ipv6
+            .iter()
+            .filter(|address| address.global)
+            .find(|address| Some(&address.interface) == default_interface.as_ref())
+            .or_else(|| ipv6.iter().find(|address| address.global))
+            .map(|address| address.address.to_string());
+
+        let (search_domains, nameservers) = self.resolv_conf();
+
+        let mut contexts = vec![
+            Context::ListContext(
+                String::from("interfaces"),
+                interfaces.iter().cloned().map(Into::into).collect(),
+            ),
+            Context::KeyValueContext(
+                String::from("default_interface"),
+                default_interface.unwrap_or_default().into(),
+            ),
+            Context::KeyValueContext(
+                String::from("default_gateway"),
+                default_route
+                    .map(|route| route.gateway.to_string())
+                    .unwrap_or_default()
+                    .into(),
+            ),
+            Context::KeyValueContext(
+                String::from("primary_ipv4"),
+                primary_ipv4.unwrap_or_default().into(),
+            ),
+            Context::KeyValueContext(
+                String::from("primary_ipv6"),
+                primary_ipv6.unwrap_or_default().into(),
+            ),
+            Context::ListContext(
+                String::from("search_domains"),
+                search_domains.into_iter().map(Into::into).collect(),
+            ),
+            Context::ListContext(
+                String::from("nameservers"),
+                nameservers.into_iter().map(Into::into).collect(),
+            ),
+        ];
+
+        let mut addresses = vec![];
+
+        for interface in interfaces.iter() {
+            let interface_ipv4: Vec<String> = ipv4
+                .get(interface)
+                .map(|addresses| addresses.iter().map(ToString::to_string).collect())
+                .unwrap_or_default();
+
+            let interface_ipv6: Vec<String> = ipv6
+                .iter()
+                .filter(|address| &address.interface == interface)
+                .map(|address| address.address.to_string())
+                .collect();
+
+            addresses.extend(interface_ipv4.iter().cloned());
+            addresses.extend(interface_ipv6.iter().cloned());
+
+            // Names like `br-1a2b` or `eth0.100` aren't valid identifiers
+            let key: String = interface
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+
+            contexts.push(Context::ListContext(
+                format!("ipv4_{key}"),
+                interface_ipv4.into_iter().map(Into::into).collect(),
+            ));
+            contexts.push(Context::ListContext(
+                format!("ipv6_{key}"),
+                interface_ipv6.into_iter().map(Into::into).collect(),
+            ));
+        }
+
+        contexts.push(Context::ListContext(
+            String::from("addresses"),
+            addresses.into_iter().map(Into::into).collect(),
+        ));
+
+        Ok(contexts)
+    }
+}
+
+impl NetworkContextProvider {
+    fn read(&self, path: &str) -> String {
+        std::fs::read_to_string(self.root.join(path)).unwrap_or_default()
+    }
+
+    fn interfaces(&self) -> Vec<String> {
+        // The first two lines of /proc/net/dev are headers
+        self.read("proc/net/dev")
+            .lines()
+            .skip(2)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(interface, _)| interface.trim().to_string())
+            .collect()
+    }
+
+    fn routes(&self) -> Vec<Route> {
+        // Addresses in /proc/net/route are hex of the address in the
+        // host's byte order
+        fn address(hex: &str) -> Option<Ipv4Addr> {
+            u32::from_str_radix(hex, 16)
+                .ok()
+                .map(|address| Ipv4Addr::from(address.to_ne_bytes()))
+        }
+
+        self.read("proc/net/route")
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+
+                if fields.len() < 8 {
+                    return None;
+                }
+
+                Some(Route {
+                    interface: fields[0].to_string(),
+                    destination: address(fields[1])?,
+                    gateway: address(fields[2])?,
+                    metric: fields[6].parse().ok()?,
+                    mask: address(fields[7])?,
+                })
+            })
+            .collect()
+    }
+
+    /// Local addresses are the `/32 host LOCAL` leaves of the routing trie,
+    /// assigned to the interface of the most specific route that contains them
+    fn ipv4_addresses(&self, routes: &[Route]) -> BTreeMap<String, Vec<Ipv4Addr>> {
+        let mut addresses: BTreeMap<String, Vec<Ipv4Addr>> = BTreeMap::new();
+        let mut leaf: Option<Ipv4Addr> = None;
+
+        for line in self.read("proc/net/fib_trie").lines() {
+            let line = line.trim();
+
+            if let Some(address) = line.strip_prefix("|-- ") {
+                leaf = address.parse().ok();
+                continue;
+            }
+
+            let Some(address) = leaf else {
+                continue;
+            };
+
+            if !(line.starts_with("/32") && line.ends_with("host LOCAL")) {
+                continue;
+            }
+
+            let interface = if address.is_loopback() {
+                Some(String::from("lo"))
+            } else {
+                routes
+                    .iter()
+                    .filter(|route| !route.mask.is_unspecified())
+                    .filter(|route| {
+                        u32::from(address) & u32::from(route.mask) == u32::from(route.destination)
+                    })
+                    .max_by_key(|route| u32::from(route.mask).count_ones())
+                    .map(|route| route.interface.clone())
+            };
+
+            if let Some(interface) = interface {
+                let interface_addresses = addresses.entry(interface).or_default();
+
+                if !interface_addresses.contains(&address) {
+                    interface_addresses.push(address);
+                }
+            }
+        }
+
+        addresses
+    }
+
+    fn ipv6_addresses(&self) -> Vec<Ipv6Address> {
+        self.read("proc/net/if_inet6")
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+
+                if fields.len() < 6 {
+                    return None;
+                }
+
+                let address = u128::from_str_radix(fields[0], 16).ok()?;
+
+                Some(Ipv6Address {
+                    interface: fields[5].to_string(),
+                    address: Ipv6Addr::from(address),
+                    global: fields[3] == "00",
+                })
+            })
+            .collect()
+    }
+
+    fn resolv_conf(&self) -> (Vec<String>, Vec<String>) {
+        let mut search_domains = vec![];
+        let mut nameservers = vec![];
+
+        for line in self.read("etc/resolv.conf").lines() {
+            let mut fields = line.split_whitespace();
+
+            match fields.next() {
+                Some("search") | Some("domain") => {
+                    for domain in fields {
+                        if !search_domains.iter().any(|known| known == domain) {
+                            search_domains.push(domain.to_string());
+                        }
+                    }
+                }
+                Some("nameserver") => nameservers.extend(fields.next().map(String::from)),
+                _ => (),
+            }
+        }
+
+        (search_domains, nameservers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contexts::fixtures::{values, write};
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use std::path::Path;
+
+    fn network(root: &Path) -> BTreeMap<String, Value> {
+        values(&NetworkContextProvider {
+            root: root.to_path_buf(),
+        })
+    }
+
+    #[test]
+    fn it_reads_fixture_files() {
+        let root = tempfile::tempdir().unwrap();
+
+        write(
+            root.path(),
+            "proc/net/dev",
+            "Inter-|   Receive\n face |bytes\n    lo: 0 0\n  eth0: 0 0\n wlan0: 0 0\neth0.100: 0 0\n",
+        );
+        write(
+            root.path(),
+            "proc/net/route",
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+             wlan0\t00000000\t0100A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n\
+             eth0\t00000000\t01000A0A\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+             eth0\t00000A0A\t00000000\t0001\t0\t0\t100\t0000FFFF\t0\t0\t0\n\
+             wlan0\t0000A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\t0\t0\t0\n",
+        );
+        write(
+            root.path(),
+            "proc/net/fib_trie",
+            "Main:\n  +-- 0.0.0.0/0 3 0 5\n     |-- 0.0.0.0\n        /0 universe UNICAST\n\
+             \x20    +-- 10.10.0.0/16 2 0 2\n        |-- 10.10.4.2\n           /32 host LOCAL\n\
+             \x20    +-- 127.0.0.0/8 2 0 2\n        |-- 127.0.0.1\n           /32 host LOCAL\n\
+             \x20    |-- 192.168.0.23\n        /32 host LOCAL\n\
+             \x20    |-- 192.168.0.255\n        /32 link BROADCAST\n\
+             Local:\n     |-- 10.10.4.2\n        /32 host LOCAL\n",
+        );
+        write(
+            root.path(),
+            "proc/net/if_inet6",
+            "00000000000000000000000000000001 01 80 10 80       lo\n\
+             fe800000000000000000000000000002 02 40 20 80     eth0\n\
+             20010db8000000000000000000000002 02 40 00 80     eth0\n\
+             20010db8000000000000000000000100 03 40 00 80 eth0.100\n",
+        );
+        write(
+            root.path(),
+            "etc/resolv.conf",
+            "# Generated\nsearch corp.example.com example.com\nnameserver 10.10.0.53\noptions edns0\n",
+        );
+
+        let network = network(root.path());
+
+        assert_eq!("lo,eth0,wlan0,eth0.100", network["interfaces"].to_string());
+        assert_eq!("eth0", network["default_interface"].to_string());
+        assert_eq!("10.10.0.1", network["default_gateway"].to_string());
+        assert_eq!("10.10.4.2", network["primary_ipv4"].to_string());
+        assert_eq!("2001:db8::2", network["primary_ipv6"].to_string());
+        assert_eq!("192.168.0.23", network["ipv4_wlan0"].to_string());
+        assert_eq!("127.0.0.1", network["ipv4_lo"].to_string());
+        assert_eq!("fe80::2,2001:db8::2", network["ipv6_eth0"].to_string());
+        assert_eq!("2001:db8::100", network["ipv6_eth0_100"].to_string());
+        assert_eq!(
+            "corp.example.com,example.com",
+            network["search_domains"].to_string()
+        );
+        assert_eq!("10.10.0.53", network["nameservers"].to_string());
+    }
+
+    #[test]
+    fn it_is_empty_without_fixture_files() {
+        let root = tempfile::tempdir().unwrap();
+
+        let network = network(root.path());
+
+        assert_eq!("", network["interfaces"].to_string());
+        assert_eq!("", network["default_interface"].to_string());
+        assert_eq!("", network["primary_ipv4"].to_string());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::contexts::secrets::register_secret;
+use crate::contexts::{Context, ContextProvider};
+use crate::utilities::write_private;
+use crate::values::Value;
+use age::secrecy::ExposeSecret;
+use age::x25519::Identity;
+use anyhow::{anyhow, Context as _, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// A value asked for once per machine, at the start of an apply
+#[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Prompt {
+    /// The question, defaults to the name of the prompt
+    #[serde(default)]
+    pub message: Option<String>,
+
+    #[serde(default, rename = "type")]
+    pub kind: PromptType,
+
+    #[serde(default)]
+    pub default: Option<String>,
+
+    /// Restricts the answer to one of these values
+    #[serde(default)]
+    pub choices: Vec<String>,
+
+    /// Secrets aren't echoed and are stored encrypted
+    #[serde(default)]
+    pub secret: bool,
+}
+
+#[derive(JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptType {
+    #[default]
+    String,
+    Integer,
+    Boolean,
+}
+
+impl Prompt {
+    /// Checks an answer against the type and choices of the prompt,
+    /// returning it in its canonical form
+    pub fn validate(&self, answer: &str) -> Result<String> {
+        let answer = answer.trim();
+
+        if !self.choices.is_empty() && !self.choices.iter().any(|choice| choice == answer) {
+            return Err(anyhow!(
+                "'{answer}' isn't one of {}",
+                self.choices.join(", ")
+            ));
+        }
+
+        match self.kind {
+            PromptType::String => Ok(answer.to_string()),
+            PromptType::Integer => Ok(answer
+                .parse::<i64>()
+                .map_err(|_| anyhow!("'{answer}' isn't an integer"))?
+                .to_string()),
+            PromptType::Boolean => match answer.to_lowercase().as_str() {
+                "true" | "yes" | "y" | "on" | "1" => Ok(String::from("true")),
+                "false" | "no" | "n" | "off" | "0" => Ok(String::from("false")),
+                _ => Err(anyhow!("'{answer}' isn't a boolean")),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct Answer {
+    #[serde(default, rename = "type")]
+    kind: PromptType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+
+    /// Armored age ciphertext of secret answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encrypted: Option<String>,
+}
+
+/// Answers to prompts, stored per user. Secrets are encrypted with an age
+/// identity that is generated on first use and kept next to the answers.
+/// Anyone who can read the answers can read the identity too, so this only
+/// keeps secrets from showing in plain text, like when the file is opened or
+/// shared by accident. It doesn't protect them.
+pub struct PromptStore {
+    pub dir: PathBuf,
+    answers: BTreeMap<String, Answer>,
+}
+
+impl PromptStore {
+    /// The store in the platform's local data directory, if there is one
+    pub fn new() -> Option<Result<PromptStore>> {
+        dirs_next::data_local_dir()
+            .map(|dir| PromptStore::open(dir.join("comtrya").join("prompts")))
+    }
+
+    pub fn open(dir: PathBuf) -> Result<PromptStore> {
+        let path = dir.join("answers.yaml");
+
+        let answers = match path.exists() {
+            true => serde_yaml_ng::from_str(&std::fs::read_to_string(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            false => BTreeMap::new(),
+        };
+
+        Ok(PromptStore { dir, answers })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.answers.contains_key(name)
+    }
+
+    /// The answer to a prompt, decrypted if it's a secret
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        let Some(answer) = self.answers.get(name) else {
+            return Ok(None);
+        };
+
+        match (&answer.value, &answer.encrypted) {
+            (Some(value), _) => Ok(Some(value.clone())),
+            (None, Some(encrypted)) => {
+                let plaintext = age::decrypt(&self.identity()?, encrypted.as_bytes())
+                    .with_context(|| format!("Failed to decrypt the answer to '{name}'"))?;
+
+                Ok(Some(String::from_utf8(plaintext)?))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, prompt: &Prompt, value: &str) -> Result<()> {
+        let answer = match prompt.secret {
+            true => Answer {
+                kind: prompt.kind,
+                value: None,
+                encrypted: Some(age::encrypt_and_armor(
+                    &self.identity_or_generate()?.to_public(),
+                    value.as_bytes(),
+                )?),
+            },
+            false => Answer {
+                kind: prompt.kind,
+                value: Some(value.to_string()),
+                encrypted: None,
+            },
+        };
+
+        self.answers.insert(name.to_string(), answer);
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_private(
+            &self.dir.join("answers.yaml"),
+            serde_yaml_ng::to_string(&self.answers)?,
+        )
+    }
+
+    /// Every answer, typed by its prompt
+    pub fn values(&self) -> BTreeMap<String, Value> {
+        let mut values = BTreeMap::new();
+
+        for (name, answer) in self.answers.iter() {
+            match self.get(name) {
+                Ok(Some(value)) => {
+                    if answer.encrypted.is_some() {
+                        register_secret(&value);
+                    }
+
+                    values.insert(name.clone(), typed(answer.kind, value));
+                }
+                Ok(None) => (),
+                Err(err) => warn!("Skipping prompt {}: {:#}", name, err),
+            }
+        }
+
+        values
+    }
+
+    fn identity(&self) -> Result<Identity> {
+        let path = self.dir.join("identity.txt");
+
+        std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read age identity {}", path.display()))?
+            .lines()
+            .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+            .ok_or_else(|| anyhow!("No age identity in {}", path.display()))?
+            .parse::<Identity>()
+            .map_err(|err| anyhow!("Invalid age identity in {}: {err}", path.display()))
+    }
+
+    fn identity_or_generate(&self) -> Result<Identity> {
+        let path = self.dir.join("identity.txt");
+
+        if path.exists() {
+            return self.identity();
+        }
+
+        let identity = Identity::generate();
+        write_private(
+            &path,
+            format!(
+                "# public key: {}\n{}\n",
+                identity.to_public(),
+                identity.to_string().expose_secret()
+            ),
+        )?;
+
+        Ok(identity)
+    }
+}
+
+pub fn typed(kind: PromptType, value: String) -> Value {
+    match kind {
+        PromptType::String => value.into(),
+        PromptType::Integer => value
+            .parse::<i64>()
+            .map(Into::into)
+            .unwrap_or_else(|_| value.into()),
+        PromptType::Boolean => (value == "true").into(),
+    }
+}
+
+/// Exposes stored answers. Prompts are asked by the app, which adds the
+/// new answers to this context.
+pub struct PromptsContextProvider {}
+
+impl ContextProvider for PromptsContextProvider {
+    fn get_prefix(&self) -> String {
+        String::from("prompts")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<Context>> {
+        let store = match PromptStore::new() {
+            Some(Ok(store)) => store,
+            Some(Err(err)) => {
+                warn!("Failed to open stored prompt answers: {:#}", err);
+                return Ok(vec![]);
+            }
+            None => return Ok(vec![]),
+        };
+
+        Ok(store
+            .values()
+            .into_iter()
+            .map(|(name, value)| Context::KeyValueContext(name, value))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_validates_answers() {
+        let prompt = Prompt {
+            kind: PromptType::Boolean,
+            ..Default::default()
+        };
+        assert_eq!("true", prompt.validate(" Yes ").unwrap());
+        assert!(prompt.validate("maybe").is_err());
+
+        let prompt = Prompt {
+            kind: PromptType::Integer,
+            ..Default::default()
+        };
+        assert_eq!("42", prompt.validate("42").unwrap());
+        assert!(prompt.validate("forty-two").is_err());
+
+        let prompt = Prompt {
+            choices: vec![String::from("server"), String::from("desktop")],
+            ..Default::default()
+        };
+        assert_eq!("desktop", prompt.validate("desktop").unwrap());
+        assert!(prompt.validate("laptop").is_err());
+    }
+
+    #[test]
+    fn it_stores_secrets_encrypted() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let mut store = PromptStore::open(dir.path().to_path_buf())?;
+        store.set("git_email", &Prompt::default(), "jack@example.com")?;
+        store.set(
+            "vpn_password",
+            &Prompt {
+                secret: true,
+                ..Default::default()
+            },
+            "kree",
+        )?;
+        store.set(
+            "vpn",
+            &Prompt {
+                kind: PromptType::Boolean,
+                ..Default::default()
+            },
+            "true",
+        )?;
+        store.save()?;
+
+        let answers = std::fs::read_to_string(dir.path().join("answers.yaml"))?;
+        assert!(answers.contains("jack@example.com"));
+        assert!(!answers.contains("kree"));
+
+        let store = PromptStore::open(dir.path().to_path_buf())?;
+        assert!(store.contains("vpn_password"));
+        assert_eq!(Some(String::from("kree")), store.get("vpn_password")?);
+
+        let values = store.values();
+        assert_eq!("jack@example.com", values["git_email"].to_string());
+        assert_eq!(Value::from(true), values["vpn"]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.path().join("identity.txt"))?
+                .permissions()
+                .mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+
+        Ok(())
+    }
+}
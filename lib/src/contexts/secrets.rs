@@ -0,0 +1,200 @@
+use crate::config::Config;
+use crate::contexts::{Context, ContextProvider};
+use crate::encryption::{self, Encryption};
+use anyhow::{anyhow, Context as _, Result};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// What secret values are replaced with in output
+pub const REDACTED: &str = "********";
+
+/// Values shorter than this aren't redacted, as they'd mangle unrelated output
+const MIN_REDACTED_LEN: usize = 4;
+
+static SECRET_VALUES: RwLock<Vec<String>> = RwLock::new(vec![]);
+
+/// Registers a value to be replaced by [`redact`]
+pub fn register_secret(value: &str) {
+    if value.len() < MIN_REDACTED_LEN {
+        return;
+    }
+
+    let mut values = SECRET_VALUES.write().unwrap_or_else(|err| err.into_inner());
+
+    if !values.iter().any(|known| known == value) {
+        values.push(value.to_string());
+        // Longest first, so a secret containing another one is replaced whole
+        values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    }
+}
+
+/// Replaces every registered secret value in `text`
+pub fn redact(text: &str) -> String {
+    let values = SECRET_VALUES.read().unwrap_or_else(|err| err.into_inner());
+
+    values.iter().fold(text.to_string(), |text, value| {
+        text.replace(value, REDACTED)
+    })
+}
+
+/// Whether a variable holds an age encrypted value
+pub fn is_encrypted(value: &str) -> bool {
+    encryption::detect(value.as_bytes()) == Some(Encryption::Recipients)
+}
+
+/// Decrypts encrypted `variables` and the `secrets_file` of the config with
+/// its identities. Every value is registered for redaction.
+pub struct SecretsContextProvider<'a> {
+    pub config: &'a Config,
+}
+
+impl SecretsContextProvider<'_> {
+    fn secrets(&self) -> Result<BTreeMap<String, String>> {
+        let encrypted: Vec<(&String, &String)> = self
+            .config
+            .variables
+            .iter()
+            .filter(|(_, value)| is_encrypted(value))
+            .collect();
+
+        if encrypted.is_empty() && self.config.secrets_file.is_none() {
+            return Ok(BTreeMap::new());
+        }
+
+        if self.config.identities.is_empty() {
+            return Err(anyhow!(
+                "Secrets can't be decrypted without `identities` in Comtrya.yaml"
+            ));
+        }
+
+        let identities = encryption::load_identities(&self.config.identities)?;
+        let mut secrets = BTreeMap::new();
+
+        if let Some(secrets_file) = &self.config.secrets_file {
+            let encrypted = std::fs::read(secrets_file)
+                .with_context(|| format!("Failed to read secrets file {secrets_file}"))?;
+            let decrypted = encryption::decrypt(&identities, &encrypted)
+                .with_context(|| format!("Failed to decrypt secrets file {secrets_file}"))?;
+
+            let file: BTreeMap<String, String> = serde_yaml_ng::from_slice(&decrypted)
+                .with_context(|| format!("Secrets file {secrets_file} isn't a map of strings"))?;
+
+            secrets.extend(file);
+        }
+
+        for (name, value) in encrypted {
+            let decrypted = encryption::decrypt(&identities, value.as_bytes())
+                .with_context(|| format!("Failed to decrypt variable {name}"))?;
+
+            secrets.insert(name.clone(), String::from_utf8(decrypted)?);
+        }
+
+        Ok(secrets)
+    }
+}
+
+impl ContextProvider for SecretsContextProvider<'_> {
+    fn get_prefix(&self) -> String {
+        String::from("secrets")
+    }
+
+    fn get_contexts(&self) -> Result<Vec<Context>> {
+        Ok(self
+            .secrets()?
+            .into_iter()
+            .map(|(name, value)| {
+                register_secret(&value);
+                Context::KeyValueContext(name, value.into())
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contexts::build_contexts;
+    use age::secrecy::ExposeSecret;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_decrypts_variables_and_secrets_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let identity = age::x25519::Identity::generate();
+        let identity_path = dir.path().join("identity.txt");
+        std::fs::write(&identity_path, identity.to_string().expose_secret())?;
+
+        let recipients = encryption::parse_recipients(&[identity.to_public().to_string()])?;
+
+        let secrets_path = dir.path().join("secrets.yaml.age");
+        std::fs::write(
+            &secrets_path,
+            encryption::encrypt(&recipients, b"gate_code: chevron-seven-locked\n")?,
+        )?;
+
+        let mut variables = BTreeMap::new();
+        variables.insert(String::from("planet"), String::from("Abydos"));
+        variables.insert(
+            String::from("api_token"),
+            String::from_utf8(encryption::encrypt(&recipients, b"kree-sha-token")?)?,
+        );
+
+        let config = Config {
+            variables,
+            identities: vec![identity_path.display().to_string()],
+            secrets_file: Some(secrets_path.display().to_string()),
+            ..Default::default()
+        };
+
+        let contexts = build_contexts(&config);
+
+        assert_eq!(
+            "kree-sha-token",
+            contexts["secrets"]["api_token"].to_string()
+        );
+        assert_eq!(
+            "chevron-seven-locked",
+            contexts["secrets"]["gate_code"].to_string()
+        );
+        assert_eq!("Abydos", contexts["variables"]["planet"].to_string());
+        assert!(!contexts["variables"].contains_key("api_token"));
+
+        assert_eq!(
+            "token: ********, code: ********",
+            redact("token: kree-sha-token, code: chevron-seven-locked")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_requires_identities() {
+        let mut variables = BTreeMap::new();
+        variables.insert(
+            String::from("api_token"),
+            String::from("-----BEGIN AGE ENCRYPTED FILE-----"),
+        );
+
+        let config = Config {
+            variables,
+            ..Default::default()
+        };
+
+        // Not valid age content, so it's a plain variable
+        assert!(SecretsContextProvider { config: &config }
+            .get_contexts()
+            .unwrap()
+            .is_empty());
+
+        let config = Config {
+            secrets_file: Some(String::from("secrets.yaml.age")),
+            ..Default::default()
+        };
+
+        let err = SecretsContextProvider { config: &config }
+            .get_contexts()
+            .unwrap_err();
+        assert!(err.to_string().contains("identities"), "{err}");
+    }
+}
@@ -1,6 +1,12 @@
 use crate::contexts::{Context, ContextProvider};
+use crate::values::Value;
 use anyhow::Result;
-use dirs_next::{config_dir, data_dir, data_local_dir, document_dir, home_dir};
+use dirs_next::{
+    cache_dir, config_dir, data_dir, data_local_dir, desktop_dir, document_dir, download_dir,
+    executable_dir, home_dir, runtime_dir,
+};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 pub struct UserContextProvider {}
 
@@ -13,44 +19,86 @@ impl ContextProvider for UserContextProvider {
         let name = whoami::realname().unwrap_or_else(|_| String::from("unknown"));
         let username = whoami::username().unwrap_or_else(|_| String::from("unknown"));
 
+        let (group, groups) = self.get_groups();
+
         Ok(vec![
             Context::KeyValueContext(String::from("id"), self.get_uid().to_string().into()),
+            Context::KeyValueContext(String::from("gid"), self.get_gid().to_string().into()),
             Context::KeyValueContext(String::from("name"), name.into()),
             Context::KeyValueContext(String::from("username"), username.into()),
+            Context::KeyValueContext(String::from("group"), group.into()),
+            Context::ListContext(
+                String::from("groups"),
+                groups.into_iter().map(Into::into).collect(),
+            ),
+            Context::KeyValueContext(String::from("shell"), self.get_shell().into()),
+            Context::KeyValueContext(
+                String::from("passwordless_sudo"),
+                self.has_passwordless_sudo().into(),
+            ),
+            Context::KeyValueContext(String::from("home_dir"), dir(home_dir())),
+            Context::KeyValueContext(String::from("config_dir"), dir(config_dir())),
+            Context::KeyValueContext(String::from("data_dir"), dir(data_dir())),
+            Context::KeyValueContext(String::from("data_local_dir"), dir(data_local_dir())),
+            Context::KeyValueContext(String::from("cache_dir"), dir(cache_dir())),
+            Context::KeyValueContext(String::from("runtime_dir"), dir(runtime_dir())),
+            Context::KeyValueContext(String::from("executable_dir"), dir(executable_dir())),
+            Context::KeyValueContext(String::from("document_dir"), dir(document_dir())),
+            Context::KeyValueContext(String::from("desktop_dir"), dir(desktop_dir())),
+            Context::KeyValueContext(String::from("download_dir"), dir(download_dir())),
             Context::KeyValueContext(
-                String::from("home_dir"),
-                home_dir()
-                    .map(Into::into)
-                    .unwrap_or_else(|| "unknown".into()),
+                String::from("xdg_config_home"),
+                dir(xdg_base_dir("XDG_CONFIG_HOME", ".config")),
             ),
             Context::KeyValueContext(
-                String::from("config_dir"),
-                config_dir()
-                    .map(Into::into)
-                    .unwrap_or_else(|| "unknown".into()),
+                String::from("xdg_data_home"),
+                dir(xdg_base_dir("XDG_DATA_HOME", ".local/share")),
             ),
             Context::KeyValueContext(
-                String::from("data_dir"),
-                data_dir()
-                    .map(Into::into)
-                    .unwrap_or_else(|| "unknown".into()),
+                String::from("xdg_state_home"),
+                dir(xdg_base_dir("XDG_STATE_HOME", ".local/state")),
             ),
             Context::KeyValueContext(
-                String::from("data_local_dir"),
-                data_local_dir()
-                    .map(Into::into)
-                    .unwrap_or_else(|| "unknown".into()),
+                String::from("xdg_cache_home"),
+                dir(xdg_base_dir("XDG_CACHE_HOME", ".cache")),
             ),
             Context::KeyValueContext(
-                String::from("document_dir"),
-                document_dir()
-                    .map(Into::into)
-                    .unwrap_or_else(|| "unknown".into()),
+                String::from("xdg_bin_home"),
+                dir(xdg_base_dir("XDG_BIN_HOME", ".local/bin")),
+            ),
+            Context::KeyValueContext(
+                String::from("xdg_runtime_dir"),
+                dir(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from)),
             ),
         ])
     }
 }
 
+fn dir(path: Option<PathBuf>) -> Value {
+    path.map(Into::into).unwrap_or_else(|| "unknown".into())
+}
+
+/// Resolves an XDG base directory, falling back to its default below the
+/// home directory when the variable is unset or not an absolute path.
+fn xdg_base_dir(variable: &str, default: &str) -> Option<PathBuf> {
+    resolve_xdg_base_dir(
+        std::env::var_os(variable).map(PathBuf::from),
+        home_dir(),
+        default,
+    )
+}
+
+fn resolve_xdg_base_dir(
+    value: Option<PathBuf>,
+    home: Option<PathBuf>,
+    default: &str,
+) -> Option<PathBuf> {
+    match value {
+        Some(value) if value.is_absolute() => Some(value),
+        _ => home.map(|home| home.join(default)),
+    }
+}
+
 impl UserContextProvider {
     #[cfg(unix)]
     fn get_uid(&self) -> u32 {
@@ -61,4 +109,156 @@ impl UserContextProvider {
     fn get_uid(&self) -> u32 {
         0
     }
+
+    #[cfg(unix)]
+    fn get_gid(&self) -> u32 {
+        uzers::get_current_gid()
+    }
+
+    #[cfg(not(unix))]
+    fn get_gid(&self) -> u32 {
+        0
+    }
+
+    /// The primary group and the names of every group the user is in
+    #[cfg(unix)]
+    fn get_groups(&self) -> (String, Vec<String>) {
+        let group = uzers::get_group_by_gid(self.get_gid())
+            .map(|group| group.name().to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("unknown"));
+
+        let groups = uzers::get_user_by_uid(self.get_uid())
+            .and_then(|user| uzers::get_user_groups(user.name(), user.primary_group_id()))
+            .map(|groups| {
+                let mut names: Vec<String> = groups
+                    .iter()
+                    .map(|group| group.name().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            })
+            .unwrap_or_default();
+
+        (group, groups)
+    }
+
+    #[cfg(not(unix))]
+    fn get_groups(&self) -> (String, Vec<String>) {
+        (String::from("unknown"), vec![])
+    }
+
+    /// The login shell from the passwd database
+    #[cfg(unix)]
+    fn get_shell(&self) -> String {
+        use uzers::os::unix::UserExt;
+
+        uzers::get_user_by_uid(self.get_uid())
+            .map(|user| user.shell().display().to_string())
+            .unwrap_or_else(|| String::from("unknown"))
+    }
+
+    #[cfg(not(unix))]
+    fn get_shell(&self) -> String {
+        std::env::var("COMSPEC").unwrap_or_else(|_| String::from("unknown"))
+    }
+
+    /// Root never needs a password, everyone else has their sudo rules listed
+    fn has_passwordless_sudo(&self) -> bool {
+        if cfg!(unix) && self.get_uid() == 0 {
+            return true;
+        }
+
+        let Ok(sudo) = which::which("sudo") else {
+            return false;
+        };
+
+        is_passwordless(&sudo)
+    }
+}
+
+/// Listing rules runs nothing as root, unlike `sudo -n true`, so it doesn't
+/// show up as a failed attempt in the auth logs when a password is needed
+fn is_passwordless(sudo: &Path) -> bool {
+    Command::new(sudo)
+        .args(["-n", "-l"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .is_some_and(|output| allows_all_without_password(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether the rules of `sudo -l` allow every command without a password,
+/// like `(ALL : ALL) NOPASSWD: ALL`
+fn allows_all_without_password(rules: &str) -> bool {
+    rules.lines().any(|line| {
+        line.split_once("NOPASSWD:").is_some_and(|(_, commands)| {
+            // Other tags, like `SETENV:`, may follow
+            let commands = commands.rsplit(':').next().unwrap_or_default();
+
+            commands.split(',').any(|command| command.trim() == "ALL")
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_reads_passwordless_sudo_rules() {
+        let rules = "User jack may run the following commands on sgc:\n    (ALL : ALL) ALL\n";
+        assert!(!allows_all_without_password(rules));
+
+        let rules = "    (ALL) NOPASSWD: /usr/bin/apt\n    (ALL) NOPASSWD: SETENV: ALL\n";
+        assert!(allows_all_without_password(rules));
+    }
+
+    #[test]
+    fn it_resolves_xdg_base_dirs() {
+        let home = Some(PathBuf::from("/home/jack"));
+
+        assert_eq!(
+            Some(PathBuf::from("/home/jack/.local/state")),
+            resolve_xdg_base_dir(None, home.clone(), ".local/state")
+        );
+        assert_eq!(
+            Some(PathBuf::from("/var/state")),
+            resolve_xdg_base_dir(
+                Some(PathBuf::from("/var/state")),
+                home.clone(),
+                ".local/state"
+            )
+        );
+        // Relative paths are invalid and ignored
+        assert_eq!(
+            Some(PathBuf::from("/home/jack/.cache")),
+            resolve_xdg_base_dir(Some(PathBuf::from("cache")), home, ".cache")
+        );
+        assert_eq!(None, resolve_xdg_base_dir(None, None, ".cache"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_lists_groups_and_shell() -> anyhow::Result<()> {
+        let contexts = UserContextProvider {}.get_contexts()?;
+
+        let groups = contexts.iter().find_map(|context| match context {
+            Context::ListContext(key, groups) if key == "groups" => Some(groups),
+            _ => None,
+        });
+        let group = contexts.iter().find_map(|context| match context {
+            Context::KeyValueContext(key, group) if key == "group" => Some(group.to_string()),
+            _ => None,
+        });
+
+        assert!(groups
+            .unwrap()
+            .contains(&Value::from(group.unwrap().as_str())));
+
+        Ok(())
+    }
 }
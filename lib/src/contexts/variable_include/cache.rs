@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::VariableIncludeSpec;
+use crate::utilities::write_private;
+
+/// On disk cache of resolved variable includes, keyed by URL and arguments
+pub struct IncludeCache {
+    pub dir: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub fetched_at: u64,
+    pub values: HashMap<String, String>,
+}
+
+impl CacheEntry {
+    pub fn age(&self) -> Duration {
+        let fetched_at = UNIX_EPOCH + Duration::from_secs(self.fetched_at);
+
+        SystemTime::now()
+            .duration_since(fetched_at)
+            .unwrap_or_default()
+    }
+}
+
+impl IncludeCache {
+    /// The cache in the platform's cache directory, if there is one
+    pub fn new() -> Option<IncludeCache> {
+        dirs_next::cache_dir().map(|dir| IncludeCache {
+            dir: dir.join("comtrya").join("includes"),
+        })
+    }
+
+    fn path(&self, spec: &VariableIncludeSpec) -> PathBuf {
+        let key = std::iter::once(spec.url.as_str())
+            .chain(spec.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\0");
+
+        self.dir.join(format!("{}.json", sha256::digest(key)))
+    }
+
+    pub fn get(&self, spec: &VariableIncludeSpec) -> Option<CacheEntry> {
+        let contents = std::fs::read_to_string(self.path(spec)).ok()?;
+
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn put(&self, spec: &VariableIncludeSpec, values: &HashMap<String, String>) -> Result<()> {
+        let entry = CacheEntry {
+            url: spec.url.clone(),
+            fetched_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            values: values.clone(),
+        };
+
+        // Values of authenticated sources or commands may be secrets
+        write_private(&self.path(spec), serde_json::to_string(&entry)?)?;
+
+        Ok(())
+    }
+}
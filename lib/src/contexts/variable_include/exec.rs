@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+
+use super::{format, VariableIncludeSpec};
+
+/// Runs the command at the path of `url` and parses its stdout
+pub fn values(
+    url: &Url,
+    format: &str,
+    spec: &VariableIncludeSpec,
+    contexts: &mut HashMap<String, String>,
+) -> Result<()> {
+    let command = url.path();
+
+    let output = Command::new(command).args(&spec.args).output()?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Command {command} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    format::values(format, &String::from_utf8(output.stdout)?, contexts)
+}
@@ -2,30 +2,59 @@ use std::collections::HashMap;
 
 use anyhow::Result;
 use reqwest::Url;
-use toml::Value;
 
-pub fn toml_values(url: &Url, contexts: &mut HashMap<String, String>) -> Result<()> {
+use super::format;
+
+pub fn values(url: &Url, format: &str, contexts: &mut HashMap<String, String>) -> Result<()> {
     let path = url.path();
 
     let contents = std::fs::read_to_string(path)?;
-    let values: HashMap<String, Value> = toml::from_str(&contents)?;
+
+    match format {
+        "toml" | "yaml" => toml_values(format, &contents, contexts),
+        format => format::values(format, &contents, contexts),
+    }
+}
+
+/// `file+toml` and `file+yaml` predate the other includes, and keep their
+/// values as TOML, with strings in quotes, so existing templates still work
+fn toml_values(format: &str, contents: &str, contexts: &mut HashMap<String, String>) -> Result<()> {
+    let values: HashMap<String, toml::Value> = match format {
+        "toml" => toml::from_str(contents)?,
+        _ => serde_yaml_ng::from_str(contents)?,
+    };
 
     for (key, value) in values {
-        contexts.insert(key.to_string(), value.to_string());
+        contexts.insert(key, value.to_string());
     }
 
     Ok(())
 }
 
-pub fn yaml_values(url: &Url, contexts: &mut HashMap<String, String>) -> Result<()> {
-    let path = url.path();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
 
-    let contents = std::fs::read_to_string(path)?;
-    let values: HashMap<String, Value> = serde_yaml_ng::from_str(&contents)?;
+    #[test]
+    fn it_keeps_strings_of_toml_and_yaml_files_quoted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut contexts = HashMap::new();
 
-    for (key, value) in values {
-        contexts.insert(key.to_string(), value.to_string());
-    }
+        for (name, contents) in [("a.toml", "name = \"Carter\""), ("b.yaml", "rank: 3")] {
+            std::fs::write(dir.path().join(name), contents)?;
 
-    Ok(())
+            let format = name.rsplit('.').next().unwrap_or_default();
+            let url = Url::parse(&format!(
+                "file+{format}://{}",
+                dir.path().join(name).display()
+            ))?;
+            values(&url, format, &mut contexts)?;
+        }
+
+        assert_eq!("\"Carter\"", contexts["name"]);
+        assert_eq!("3", contexts["rank"]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+/// Parses `contents` in the given format into flat key/value pairs.
+/// Nested values are kept in their serialized form.
+pub fn values(format: &str, contents: &str, contexts: &mut HashMap<String, String>) -> Result<()> {
+    match format {
+        "toml" => {
+            let values: HashMap<String, toml::Value> = toml::from_str(contents)?;
+
+            for (key, value) in values {
+                let value = match value {
+                    toml::Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                contexts.insert(key, value);
+            }
+        }
+        "yaml" | "yml" => {
+            let values: HashMap<String, serde_yaml_ng::Value> = serde_yaml_ng::from_str(contents)?;
+
+            for (key, value) in values {
+                contexts.insert(key, yaml_to_string(value)?);
+            }
+        }
+        "json" => {
+            let values: HashMap<String, serde_json::Value> = serde_json::from_str(contents)?;
+
+            for (key, value) in values {
+                let value = match value {
+                    serde_json::Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                contexts.insert(key, value);
+            }
+        }
+        "env" => env_values(contents, contexts),
+        format => return Err(anyhow!("Unknown variable include format: {format}")),
+    }
+
+    Ok(())
+}
+
+fn yaml_to_string(value: serde_yaml_ng::Value) -> Result<String> {
+    Ok(match value {
+        serde_yaml_ng::Value::String(value) => value,
+        serde_yaml_ng::Value::Number(value) => value.to_string(),
+        serde_yaml_ng::Value::Bool(value) => value.to_string(),
+        serde_yaml_ng::Value::Null => String::from(""),
+        value => serde_json::to_string(&value)?,
+    })
+}
+
+/// Parses dotenv style `KEY=value` lines, ignoring blank lines and comments
+fn env_values(contents: &str, contexts: &mut HashMap<String, String>) {
+    for line in contents.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|value| value.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+
+            contexts.insert(key.trim().to_string(), value.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_parses_every_format() -> anyhow::Result<()> {
+        let mut contexts = HashMap::new();
+
+        values("toml", "name = \"Carter\"\nrank = 3", &mut contexts)?;
+        assert_eq!("Carter", contexts["name"]);
+        assert_eq!("3", contexts["rank"]);
+
+        values("yaml", "name: Teal'c\nactive: true", &mut contexts)?;
+        assert_eq!("Teal'c", contexts["name"]);
+        assert_eq!("true", contexts["active"]);
+
+        values(
+            "json",
+            r#"{"name": "Jackson", "team": ["SG-1"]}"#,
+            &mut contexts,
+        )?;
+        assert_eq!("Jackson", contexts["name"]);
+        assert_eq!(r#"["SG-1"]"#, contexts["team"]);
+
+        values(
+            "env",
+            "# comment\nexport NAME=\"O'Neill\"\n\nGATE='Milky Way'\nADDRESS=earth",
+            &mut contexts,
+        )?;
+        assert_eq!("O'Neill", contexts["NAME"]);
+        assert_eq!("Milky Way", contexts["GATE"]);
+        assert_eq!("earth", contexts["ADDRESS"]);
+
+        assert!(values("ini", "", &mut contexts).is_err());
+
+        Ok(())
+    }
+}
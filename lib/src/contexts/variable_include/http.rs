@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+
+use super::{format, VariableIncludeSpec};
+use crate::http::{self, Request, Value};
+
+pub fn values(
+    url: &Url,
+    transport: &str,
+    format: &str,
+    spec: &VariableIncludeSpec,
+    contexts: &mut HashMap<String, String>,
+) -> Result<()> {
+    // Strip the format from the scheme, e.g. https+json -> https
+    let url = url.as_str().replacen(url.scheme(), transport, 1);
+
+    let mut request = Request::default();
+
+    if let Some(env) = &spec.auth_env {
+        if std::env::var_os(env).is_none() {
+            return Err(anyhow!("Environment variable {env} for {url} is not set"));
+        }
+
+        request
+            .options
+            .headers
+            .insert(spec.auth_header.clone(), Value::Env { env: env.clone() });
+    }
+
+    let contents = http::get(&url, &request)?.text()?;
+
+    format::values(format, &contents, contexts)
+}
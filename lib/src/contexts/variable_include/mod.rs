@@ -1,12 +1,94 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+
+use cache::IncludeCache;
 
 use crate::{config::Config, contexts::Context, contexts::ContextProvider};
 
+pub mod cache;
 pub mod dns;
+pub mod exec;
 pub mod file;
+pub mod format;
+pub mod http;
+
+/// An entry of `include_variables`, either a plain URL or a URL with options
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum VariableInclude {
+    Url(String),
+    Spec(VariableIncludeSpec),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VariableIncludeSpec {
+    pub url: String,
+
+    /// Prefix for every key of this include, joined with `_`
+    #[serde(default)]
+    pub namespace: Option<String>,
+
+    /// Failures of optional includes are logged and skipped
+    #[serde(default)]
+    pub optional: bool,
+
+    /// Arguments passed to the command of `exec+` includes
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Name of the header the value of `auth_env` is sent in, for `http(s)+` includes
+    #[serde(default = "default_auth_header")]
+    pub auth_header: String,
+
+    /// Environment variable holding the value of `auth_header`
+    #[serde(default)]
+    pub auth_env: Option<String>,
+
+    /// Seconds a cached result is used before the source is queried again,
+    /// overrides `include_variables_ttl`
+    #[serde(default)]
+    pub ttl: Option<u64>,
+}
+
+fn default_auth_header() -> String {
+    String::from("Authorization")
+}
+
+impl Default for VariableIncludeSpec {
+    fn default() -> Self {
+        VariableIncludeSpec {
+            url: String::new(),
+            namespace: None,
+            optional: false,
+            args: vec![],
+            auth_header: default_auth_header(),
+            auth_env: None,
+            ttl: None,
+        }
+    }
+}
+
+impl VariableInclude {
+    pub fn spec(&self) -> VariableIncludeSpec {
+        match self {
+            VariableInclude::Url(url) => VariableIncludeSpec {
+                url: url.clone(),
+                ..Default::default()
+            },
+            VariableInclude::Spec(spec) => spec.clone(),
+        }
+    }
+}
+
+impl From<&str> for VariableInclude {
+    fn from(url: &str) -> Self {
+        VariableInclude::Url(url.to_string())
+    }
+}
 
 pub struct VariableIncludeContextProvider<'a> {
     pub config: &'a Config,
@@ -18,26 +100,7 @@ impl<'a> ContextProvider for VariableIncludeContextProvider<'a> {
     }
 
     fn get_contexts(&self) -> Result<Vec<super::Context>> {
-        let mut contexts = HashMap::<String, String>::new();
-
-        if let Some(variable_includes) = &self.config.include_variables {
-            for variable_include in variable_includes {
-                let url = Url::parse(variable_include)?;
-
-                if url.scheme() == "dns+txt" {
-                    dns::txt_record_values(&url, &mut contexts)?;
-                } else if url.scheme() == "file+toml" {
-                    file::toml_values(&url, &mut contexts)?;
-                } else if url.scheme() == "file+yaml" {
-                    file::yaml_values(&url, &mut contexts)?;
-                } else {
-                    return Err(anyhow::anyhow!(
-                        "Unknown variable include scheme: {}",
-                        url.scheme()
-                    ));
-                }
-            }
-        }
+        let contexts = resolve(self.config, IncludeCache::new().as_ref())?;
 
         let contexts = contexts
             .into_iter()
@@ -47,3 +110,243 @@ impl<'a> ContextProvider for VariableIncludeContextProvider<'a> {
         Ok(contexts)
     }
 }
+
+impl VariableIncludeSpec {
+    /// Local files are always read, everything else goes through the cache
+    pub fn is_cacheable(&self) -> bool {
+        !self.url.starts_with("file+")
+    }
+}
+
+/// Resolves every include of `config`. Results of remote includes are
+/// served from `cache` while younger than their TTL, and used as a
+/// fallback when the source can't be reached. Includes that fail are
+/// reported and left out, keeping the values of the others.
+pub fn resolve(config: &Config, cache: Option<&IncludeCache>) -> Result<HashMap<String, String>> {
+    let mut contexts = HashMap::<String, String>::new();
+
+    let Some(variable_includes) = &config.include_variables else {
+        return Ok(contexts);
+    };
+
+    for variable_include in variable_includes {
+        let spec = variable_include.spec();
+        let cache = cache.filter(|_| spec.is_cacheable());
+        let cached = cache.and_then(|cache| cache.get(&spec));
+        let ttl = spec.ttl.unwrap_or(config.include_variables_ttl());
+
+        let values = match cached {
+            Some(entry) if !config.refresh_includes && entry.age().as_secs() < ttl => {
+                debug!("Using cached variable include {}", spec.url);
+                entry.values
+            }
+            cached => match include(&spec) {
+                Ok(values) => {
+                    if let Some(cache) = cache {
+                        if let Err(err) = cache.put(&spec, &values) {
+                            warn!("Failed to cache variable include {}: {}", spec.url, err);
+                        }
+                    }
+
+                    values
+                }
+                Err(err) => match cached {
+                    Some(entry) => {
+                        warn!(
+                            "Variable include {} failed, using values cached {}s ago: {}",
+                            spec.url,
+                            entry.age().as_secs(),
+                            err
+                        );
+                        entry.values
+                    }
+                    None if spec.optional => {
+                        warn!("Skipping optional variable include {}: {}", spec.url, err);
+                        continue;
+                    }
+                    None => {
+                        error!("Variable include {} failed: {:#}", spec.url, err);
+                        continue;
+                    }
+                },
+            },
+        };
+
+        for (key, value) in values {
+            let key = match &spec.namespace {
+                Some(namespace) => format!("{namespace}_{key}"),
+                None => key,
+            };
+
+            contexts.insert(key, value);
+        }
+    }
+
+    Ok(contexts)
+}
+
+/// Resolves a single include. The scheme of its URL is `<transport>+<format>`,
+/// e.g. `file+yaml` or `https+json`.
+pub fn include(spec: &VariableIncludeSpec) -> Result<HashMap<String, String>> {
+    let url = Url::parse(&spec.url)?;
+    let mut values = HashMap::new();
+
+    let (transport, format) = url
+        .scheme()
+        .split_once('+')
+        .ok_or_else(|| anyhow!("Unknown variable include scheme: {}", url.scheme()))?;
+
+    match (transport, format) {
+        ("dns", "txt") => dns::txt_record_values(&url, &mut values)?,
+        ("file", format) => file::values(&url, format, &mut values)?,
+        ("http" | "https", format) => http::values(&url, transport, format, spec, &mut values)?,
+        ("exec", format) => exec::values(&url, format, spec, &mut values)?,
+        _ => return Err(anyhow!("Unknown variable include scheme: {}", url.scheme())),
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contexts::build_contexts;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_can_be_deserialized() {
+        let config: Config = serde_yaml_ng::from_str(
+            r#"
+include_variables:
+  - "file+toml:///etc/comtrya.toml"
+  - url: "https+json://example.com/variables.json"
+    namespace: corp
+    optional: true
+    auth_env: CORP_TOKEN
+"#,
+        )
+        .unwrap();
+
+        let includes = config.include_variables.unwrap();
+
+        assert_eq!(
+            VariableInclude::Url(String::from("file+toml:///etc/comtrya.toml")),
+            includes[0]
+        );
+        assert_eq!(
+            VariableIncludeSpec {
+                url: String::from("https+json://example.com/variables.json"),
+                namespace: Some(String::from("corp")),
+                optional: true,
+                args: vec![],
+                auth_header: String::from("Authorization"),
+                auth_env: Some(String::from("CORP_TOKEN")),
+                ttl: None,
+            },
+            includes[1].spec()
+        );
+    }
+
+    #[test]
+    fn it_namespaces_and_skips_optional_includes() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env_file = dir.path().join("variables.env");
+        let json_file = dir.path().join("variables.json");
+        std::fs::write(&env_file, "GATE=Abydos\n")?;
+        std::fs::write(&json_file, r#"{"GATE": "Chulak"}"#)?;
+
+        let config = Config {
+            include_variables: Some(vec![
+                format!("file+env://{}", env_file.display()).as_str().into(),
+                VariableInclude::Spec(VariableIncludeSpec {
+                    url: format!("file+json://{}", json_file.display()),
+                    namespace: Some(String::from("jaffa")),
+                    ..Default::default()
+                }),
+                VariableInclude::Spec(VariableIncludeSpec {
+                    url: String::from("gopher+yaml://nowhere"),
+                    optional: true,
+                    ..Default::default()
+                }),
+            ]),
+            ..Default::default()
+        };
+
+        let contexts = build_contexts(&config);
+        let includes = contexts.get("include_variables").unwrap();
+
+        assert_eq!("Abydos", includes.get("GATE").unwrap().to_string());
+        assert_eq!("Chulak", includes.get("jaffa_GATE").unwrap().to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_includes_command_output() -> anyhow::Result<()> {
+        let values = include(&VariableIncludeSpec {
+            url: String::from("exec+json:///bin/echo"),
+            args: vec![String::from(r#"{"planet": "Dakara"}"#)],
+            ..Default::default()
+        })?;
+
+        assert_eq!("Dakara", values["planet"]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_caches_and_falls_back_to_cached_values() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let cache = IncludeCache {
+            dir: dir.path().join("cache"),
+        };
+
+        let script = dir.path().join("planet.sh");
+        std::fs::write(&script, "#!/bin/sh\necho PLANET=$(cat $0.planet)\n")?;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        std::fs::write(dir.path().join("planet.sh.planet"), "Abydos")?;
+
+        let mut config = Config {
+            include_variables: Some(vec![format!("exec+env://{}", script.display())
+                .as_str()
+                .into()]),
+            include_variables_ttl: Some(3600),
+            ..Default::default()
+        };
+
+        assert_eq!("Abydos", resolve(&config, Some(&cache))?["PLANET"]);
+
+        for entry in std::fs::read_dir(&cache.dir)? {
+            assert_eq!(0o600, entry?.metadata()?.permissions().mode() & 0o777);
+        }
+
+        // Fresh cache entries are used without running the command
+        std::fs::write(dir.path().join("planet.sh.planet"), "Chulak")?;
+        assert_eq!("Abydos", resolve(&config, Some(&cache))?["PLANET"]);
+
+        config.refresh_includes = true;
+        assert_eq!("Chulak", resolve(&config, Some(&cache))?["PLANET"]);
+
+        // Unreachable sources fall back to the cache
+        std::fs::remove_file(&script)?;
+        assert_eq!("Chulak", resolve(&config, Some(&cache))?["PLANET"]);
+        assert!(resolve(&config, None)?.is_empty());
+
+        // Other includes are kept when one fails
+        let variables = dir.path().join("variables.env");
+        std::fs::write(&variables, "GATE=Dakara\n")?;
+        config.include_variables = Some(vec![
+            format!("exec+env://{}", script.display()).as_str().into(),
+            format!("file+env://{}", variables.display())
+                .as_str()
+                .into(),
+        ]);
+        assert_eq!("Dakara", resolve(&config, None)?["GATE"]);
+
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::Result;
 
 use crate::{
     config::Config,
-    contexts::{Context, ContextProvider},
+    contexts::{secrets::is_encrypted, Context, ContextProvider},
 };
 
 pub struct VariablesContextProvider<'a> {
@@ -17,7 +17,12 @@ impl<'a> ContextProvider for VariablesContextProvider<'a> {
     fn get_contexts(&self) -> Result<Vec<super::Context>> {
         let mut contexts = vec![];
 
+        // Encrypted values are decrypted into the secrets context
         for (key, value) in self.config.variables.iter() {
+            if is_encrypted(value) {
+                continue;
+            }
+
             contexts.push(Context::KeyValueContext(
                 key.to_owned(),
                 value.to_owned().into(),
@@ -0,0 +1,543 @@
+use crate::contexts::secrets::register_secret;
+use crate::secret_backends::SecretRef;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Method, StatusCode, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// The HTTP client shared by everything that downloads
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HttpConfig {
+    /// Seconds a request may take, including reading the response
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Times a request is sent again after a connection error, a timeout or a
+    /// server error
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+
+    /// Seconds before the first retry, doubled with every retry after that
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+
+    /// Proxy for all requests. Without it, `HTTPS_PROXY`, `HTTP_PROXY` and
+    /// `NO_PROXY` of the environment are used.
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Comma separated hosts that aren't requested through `proxy`
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+
+    /// Sent with every request
+    #[serde(default)]
+    pub headers: BTreeMap<String, Value>,
+
+    /// Headers and credentials for requests to a host, by host name
+    #[serde(default)]
+    pub hosts: BTreeMap<String, RequestOptions>,
+}
+
+fn default_timeout() -> u64 {
+    300
+}
+
+fn default_connect_timeout() -> u64 {
+    30
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_retry_delay() -> u64 {
+    1
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            timeout: default_timeout(),
+            connect_timeout: default_connect_timeout(),
+            retries: default_retries(),
+            retry_delay: default_retry_delay(),
+            proxy: None,
+            no_proxy: None,
+            headers: BTreeMap::new(),
+            hosts: BTreeMap::new(),
+        }
+    }
+}
+
+/// A header value or credential, given as is or read when it's needed
+#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Plain(String),
+    Env { env: String },
+    Secret { secret: SecretRef },
+}
+
+impl Value {
+    /// The value, registered for redaction when it's read from somewhere
+    pub fn get(&self) -> Result<String> {
+        let value = match self {
+            Value::Plain(value) => return Ok(value.clone()),
+            Value::Env { env } => {
+                std::env::var(env).map_err(|_| anyhow!("Environment variable {env} is not set"))?
+            }
+            Value::Secret { secret } => secret.get()?,
+        };
+
+        register_secret(&value);
+
+        Ok(value)
+    }
+}
+
+/// A bearer token, or a username and password for basic auth
+#[derive(JsonSchema, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Auth {
+    Bearer {
+        bearer: Value,
+    },
+    Basic {
+        username: Value,
+        #[serde(default)]
+        password: Option<Value>,
+    },
+}
+
+/// Headers and credentials sent with requests
+#[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestOptions {
+    #[serde(default)]
+    pub headers: BTreeMap<String, Value>,
+
+    #[serde(default)]
+    pub auth: Option<Auth>,
+}
+
+/// Credentials as they're read when the request is sent
+enum Credentials {
+    Bearer(String),
+    Basic(String, Option<String>),
+}
+
+/// Headers and credentials of the configuration, the host and the request,
+/// each replacing those of the same name before it, so every header is sent
+/// once
+#[derive(Default)]
+struct Headers {
+    headers: HeaderMap,
+    auth: Option<Credentials>,
+}
+
+impl Headers {
+    fn add(&mut self, headers: &BTreeMap<String, Value>) -> Result<()> {
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name {name}"))?;
+
+            // A header given as is replaces credentials before it
+            if name == AUTHORIZATION {
+                self.auth = None;
+            }
+
+            let value = HeaderValue::from_str(&value.get()?)
+                .with_context(|| format!("Invalid value of header {name}"))?;
+            self.headers.insert(name, value);
+        }
+
+        Ok(())
+    }
+
+    fn add_options(&mut self, options: &RequestOptions) -> Result<()> {
+        self.add(&options.headers)?;
+
+        let auth = match &options.auth {
+            Some(Auth::Bearer { bearer }) => Credentials::Bearer(bearer.get()?),
+            Some(Auth::Basic { username, password }) => Credentials::Basic(
+                username.get()?,
+                password.as_ref().map(Value::get).transpose()?,
+            ),
+            None => return Ok(()),
+        };
+
+        self.headers.remove(AUTHORIZATION);
+        self.auth = Some(auth);
+
+        Ok(())
+    }
+
+    fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request.headers(self.headers.clone());
+
+        match &self.auth {
+            Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+            Some(Credentials::Basic(username, password)) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            None => request,
+        }
+    }
+}
+
+/// What a single request adds to, or overrides of, the shared configuration
+#[derive(Clone, Debug, Default)]
+pub struct Request {
+    pub options: RequestOptions,
+    pub timeout: Option<Duration>,
+    pub retries: Option<u32>,
+}
+
+/// The configuration, and the client built from it once
+struct Shared {
+    config: HttpConfig,
+    client: Client,
+}
+
+impl Shared {
+    fn new(config: HttpConfig) -> Result<Shared> {
+        Ok(Shared {
+            client: config.client()?,
+            config,
+        })
+    }
+}
+
+static SHARED: RwLock<Option<Arc<Shared>>> = RwLock::new(None);
+
+/// Makes all requests use `config`
+pub fn init(config: HttpConfig) -> Result<()> {
+    let shared = Arc::new(Shared::new(config)?);
+    *SHARED.write().unwrap_or_else(|err| err.into_inner()) = Some(shared);
+
+    Ok(())
+}
+
+/// What requests use, the defaults until [`init`] is called
+fn shared() -> Result<Arc<Shared>> {
+    if let Some(shared) = &*SHARED.read().unwrap_or_else(|err| err.into_inner()) {
+        return Ok(shared.clone());
+    }
+
+    let mut shared = SHARED.write().unwrap_or_else(|err| err.into_inner());
+
+    match &*shared {
+        Some(shared) => Ok(shared.clone()),
+        None => {
+            let created = Arc::new(Shared::new(HttpConfig::default())?);
+            *shared = Some(created.clone());
+
+            Ok(created)
+        }
+    }
+}
+
+impl HttpConfig {
+    fn client(&self) -> Result<Client> {
+        let mut builder = Client::builder()
+            .user_agent(concat!("comtrya/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(self.timeout))
+            .connect_timeout(Duration::from_secs(self.connect_timeout));
+
+        if let Some(proxy) = &self.proxy {
+            let no_proxy = self
+                .no_proxy
+                .as_deref()
+                .and_then(reqwest::NoProxy::from_string);
+
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy)
+                    .with_context(|| format!("Invalid proxy {proxy}"))?
+                    .no_proxy(no_proxy),
+            );
+        }
+
+        Ok(builder.build()?)
+    }
+
+    fn headers(&self, url: &Url, request: &Request) -> Result<Headers> {
+        let mut headers = Headers::default();
+        headers.add(&self.headers)?;
+
+        if let Some(host) = url.host_str().and_then(|host| self.hosts.get(host)) {
+            headers.add_options(host)?;
+        }
+
+        headers.add_options(&request.options)?;
+
+        Ok(headers)
+    }
+
+    fn build(
+        &self,
+        client: &Client,
+        method: &Method,
+        url: &Url,
+        request: &Request,
+        headers: &Headers,
+    ) -> RequestBuilder {
+        let mut builder = headers.apply(client.request(method.clone(), url.clone()));
+
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        builder
+    }
+}
+
+/// Whether sending the request again could succeed
+fn is_transient(result: &reqwest::Result<Response>) -> bool {
+    match result {
+        Ok(response) => {
+            response.status().is_server_error()
+                || response.status() == StatusCode::TOO_MANY_REQUESTS
+        }
+        Err(err) => err.is_timeout() || err.is_connect(),
+    }
+}
+
+/// Sends a request with the shared configuration, retrying transient
+/// failures. Responses with an error status are turned into errors.
+pub fn send(method: Method, url: &str, request: &Request) -> Result<Response> {
+    let shared = shared()?;
+    let (config, client) = (&shared.config, &shared.client);
+    let url = Url::parse(url).with_context(|| format!("Invalid URL {url}"))?;
+    let retries = request.retries.unwrap_or(config.retries);
+    let headers = config.headers(&url, request)?;
+
+    let mut attempt = 0;
+    loop {
+        let result = config
+            .build(client, &method, &url, request, &headers)
+            .send();
+
+        if attempt < retries && is_transient(&result) {
+            let delay =
+                Duration::from_secs(config.retry_delay.saturating_mul(1 << attempt.min(16)));
+
+            match &result {
+                Ok(response) => warn!(
+                    "{} {} returned {}, retrying in {:?}",
+                    method,
+                    url,
+                    response.status(),
+                    delay
+                ),
+                Err(err) => warn!(
+                    "{} {} failed, retrying in {:?}: {}",
+                    method, url, delay, err
+                ),
+            }
+
+            attempt += 1;
+            std::thread::sleep(delay);
+            continue;
+        }
+
+        debug!("{} {} after {} retries", method, url, attempt);
+
+        return Ok(result?.error_for_status()?);
+    }
+}
+
+pub fn get(url: &str, request: &Request) -> Result<Response> {
+    send(Method::GET, url, request)
+}
+
+pub fn head(url: &str, request: &Request) -> Result<Response> {
+    send(Method::HEAD, url, request)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers requests with `responses` in turn, repeating the last one,
+    /// and records the headers of every request
+    pub(crate) fn serve(responses: &[(u16, &str)]) -> (String, Arc<Mutex<Vec<Vec<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let responses: Vec<(u16, String)> = responses
+            .iter()
+            .map(|(status, body)| (*status, body.to_string()))
+            .collect();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+
+        std::thread::spawn(move || {
+            for (index, stream) in listener.incoming().flatten().enumerate() {
+                let mut reader = BufReader::new(&stream);
+                let mut headers = vec![];
+                let mut line = String::new();
+
+                while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                    headers.push(line.trim_end().to_string());
+                    line.clear();
+                }
+
+                recorded.lock().unwrap().push(headers);
+
+                let (status, body) = &responses[index.min(responses.len() - 1)];
+                let _ = write!(
+                    &stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: {}\r\nETag: \"stargate\"\r\nConnection: close\r\n\r\n{body}",
+                    body.len(),
+                );
+            }
+        });
+
+        (format!("http://{address}"), requests)
+    }
+
+    #[test]
+    fn it_sends_headers_and_auth() -> Result<()> {
+        let (url, requests) = serve(&[(200, "Chevron seven locked")]);
+
+        std::env::set_var("COMTRYA_TEST_HTTP_TOKEN", "Kree");
+
+        let request = Request {
+            options: RequestOptions {
+                headers: BTreeMap::from([(
+                    String::from("X-Gate"),
+                    Value::Plain(String::from("Abydos")),
+                )]),
+                auth: Some(Auth::Bearer {
+                    bearer: Value::Env {
+                        env: String::from("COMTRYA_TEST_HTTP_TOKEN"),
+                    },
+                }),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!("Chevron seven locked", get(&url, &request)?.text()?);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains(&String::from("x-gate: Abydos")));
+        assert!(requests[0].contains(&String::from("authorization: Bearer Kree")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_replaces_headers_of_the_configuration() -> Result<()> {
+        let (url, requests) = serve(&[(200, "")]);
+        let url = Url::parse(&url)?;
+
+        let config: HttpConfig = serde_yaml_ng::from_str(
+            r#"
+headers:
+  X-Gate: Chulak
+  Authorization: Basic c2djOnNnYw==
+hosts:
+  127.0.0.1:
+    auth:
+      bearer: Apophis
+"#,
+        )?;
+
+        let request = Request {
+            options: RequestOptions {
+                headers: BTreeMap::from([(
+                    String::from("X-Gate"),
+                    Value::Plain(String::from("Abydos")),
+                )]),
+                auth: Some(Auth::Bearer {
+                    bearer: Value::Plain(String::from("Kree")),
+                }),
+            },
+            ..Default::default()
+        };
+
+        let headers = config.headers(&url, &request)?;
+        config
+            .build(&config.client()?, &Method::GET, &url, &request, &headers)
+            .send()?;
+
+        let requests = requests.lock().unwrap();
+        let sent = |name: &str| -> Vec<&String> {
+            requests[0]
+                .iter()
+                .filter(|header| header.starts_with(name))
+                .collect()
+        };
+        assert_eq!(vec!["authorization: Bearer Kree"], sent("authorization:"));
+        assert_eq!(vec!["x-gate: Abydos"], sent("x-gate:"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reads_config() -> Result<()> {
+        let config: HttpConfig = serde_yaml_ng::from_str(
+            r#"
+retries: 5
+proxy: http://proxy.sgc.mil:3128
+hosts:
+  artifacts.sgc.mil:
+    auth:
+      username: jackson
+      password:
+        secret:
+          backend: pass
+          path: artifacts
+"#,
+        )?;
+
+        assert_eq!(5, config.retries);
+        assert_eq!(300, config.timeout);
+        assert_eq!(
+            Some(Auth::Basic {
+                username: Value::Plain(String::from("jackson")),
+                password: Some(Value::Secret {
+                    secret: SecretRef {
+                        backend: String::from("pass"),
+                        path: String::from("artifacts"),
+                    }
+                }),
+            }),
+            config.hosts["artifacts.sgc.mil"].auth
+        );
+        assert!(config.client().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_retries_server_errors() -> Result<()> {
+        let (url, requests) = serve(&[(503, ""), (200, "Chevron seven locked")]);
+
+        let request = Request {
+            retries: Some(1),
+            ..Default::default()
+        };
+
+        assert_eq!("Chevron seven locked", get(&url, &request)?.text()?);
+        assert_eq!(2, requests.lock().unwrap().len());
+
+        let (url, requests) = serve(&[(404, "")]);
+        assert!(get(&url, &request).is_err());
+        assert_eq!(1, requests.lock().unwrap().len());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
 pub mod actions;
 pub mod atoms;
+pub mod backups;
 pub mod config;
 pub mod contexts;
+pub mod encryption;
+pub mod http;
 pub mod manifests;
+pub mod secret_backends;
 pub mod steps;
 pub mod tera_functions;
 mod utilities;
@@ -1,20 +1,28 @@
-use super::Manifest;
+use super::{Declarations, Manifest};
 use crate::{
-    contexts::{to_tera, Contexts},
+    contexts::{prompts::typed, Contexts},
     manifests::get_manifest_name,
-    tera_functions::register_functions,
+    tera_functions::{render, templates_dir},
+    values::Value,
 };
+use anyhow::Context;
 use ignore::WalkBuilder;
+use regex::Regex;
 use std::{
-    collections::HashMap, error::Error, ffi::OsStr, fs::canonicalize, ops::Deref, path::PathBuf,
+    collections::HashMap,
+    ffi::OsStr,
+    fs::canonicalize,
+    ops::Deref,
+    path::{Path, PathBuf},
 };
-use tera::Tera;
-use tracing::{error, span};
+use tracing::{debug, error, span};
 
-pub fn load(manifest_path: PathBuf, contexts: &Contexts) -> HashMap<String, Manifest> {
-    let mut manifests: HashMap<String, Manifest> = HashMap::new();
+/// Every manifest file below `manifest_path`
+fn manifest_files(manifest_path: &Path) -> Vec<PathBuf> {
+    let mut walker = WalkBuilder::new(manifest_path);
 
-    let mut walker = WalkBuilder::new(&manifest_path);
+    // Shared templates aren't manifests
+    let templates = templates_dir();
 
     // FIXME: get rid of all .unwrap() calls
     walker
@@ -23,9 +31,14 @@ pub fn load(manifest_path: PathBuf, contexts: &Contexts) -> HashMap<String, Mani
         .same_file_system(true)
         // Arbitrary for now, 9 "should" be enough?
         .max_depth(Some(9))
-        .filter_entry(|entry| {
-            !(entry.file_type().is_some_and(|ft| ft.is_dir())
-            && entry.file_name() == OsStr::new("files"))
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+
+            !(is_dir
+                && (entry.file_name() == OsStr::new("files")
+                    || templates.as_ref().is_some_and(|templates| {
+                        canonicalize(entry.path()).is_ok_and(|path| &path == templates)
+                    })))
         })
         .build()
         // Don't walk directories
@@ -48,75 +61,264 @@ pub fn load(manifest_path: PathBuf, contexts: &Contexts) -> HashMap<String, Mani
                 })
                 .unwrap_or(false)
         })
-        .for_each(|entry| {
-            if let Ok(filename) = entry {
-                let span = span!(
-                    tracing::Level::INFO,
-                    "manifest_load",
-                    manifest = filename.file_name().to_str()
-                )
-                .entered();
-
-                let entry = canonicalize(filename.into_path()).ok().unwrap_or_default();
-                let contents =
-                    std::fs::read_to_string(entry.clone()).unwrap_or_else(|_| String::from(""));
-                let template = contents.as_str();
-
-                let mut tera = Tera::default();
-                register_functions(&mut tera);
-
-                let template = match tera.render_str(template, &to_tera(contexts)) {
-                    Ok(template) => template,
-                    Err(err) => {
-                        match err.source() {
-                            Some(err) => error!(message = err.source()),
-                            None => error!(message = err.to_string().as_str()),
-                        }
-
-                        span.exit();
-
-                        return;
-                    }
-                };
-
-                let manifest: anyhow::Result<Manifest> = match entry
-                    .extension()
-                    .and_then(OsStr::to_str)
-                {
-                    Some("yaml") | Some("yml") => {
-                        serde_yaml_ng::from_str::<Manifest>(template.deref()).map_err(anyhow::Error::from)
-                    }
-                    Some("toml") => toml::from_str::<Manifest>(template.deref()).map_err(anyhow::Error::from),
-                    _ => {
-                        error!("Unrecognized file extension for manifest");
-                        span.exit();
-
-                        return;
-                    }
-                };
-
-                match manifest {
-                    Ok(mut manifest) => {
-                        let name = get_manifest_name(&manifest_path, &entry)
-                            .expect("Failed to get manifest name");
-
-                        manifest.root_dir = entry.parent().map(|parent| parent.to_path_buf());
-
-                        manifest.name = Some(name.clone());
-
-                        manifests.insert(name, manifest);
-                    }
-                    Err(err) => {
-                        let manifest_name =
-                            get_manifest_name(&manifest_path, &entry).unwrap_or_default();
-
-                        error!("Manifest '{manifest_name}' in file with path '{}' cannot be parsed. Reason: {err}", &entry.display());
-                    }
-                }
+        .flatten()
+        .map(|entry| canonicalize(entry.into_path()).ok().unwrap_or_default())
+        .collect()
+}
+
+pub fn load(manifest_path: PathBuf, contexts: &Contexts) -> HashMap<String, Manifest> {
+    let mut manifests: HashMap<String, Manifest> = HashMap::new();
+
+    manifest_files(&manifest_path).into_iter().for_each(|entry| {
+        let span = span!(
+            tracing::Level::INFO,
+            "manifest_load",
+            manifest = entry.file_name().and_then(OsStr::to_str)
+        )
+        .entered();
+
+        let contents = std::fs::read_to_string(entry.clone()).unwrap_or_else(|_| String::from(""));
+        let template = contents.as_str();
+
+        let template = match render(template, contexts) {
+            Ok(template) => template,
+            Err(err) => {
+                error!(message = err.to_string().as_str());
 
                 span.exit();
+
+                return;
             }
-        });
+        };
+
+        let manifest: anyhow::Result<Manifest> = match entry.extension().and_then(OsStr::to_str) {
+            Some("yaml") | Some("yml") => {
+                serde_yaml_ng::from_str::<Manifest>(template.deref()).map_err(anyhow::Error::from)
+            }
+            Some("toml") => toml::from_str::<Manifest>(template.deref()).map_err(anyhow::Error::from),
+            _ => {
+                error!("Unrecognized file extension for manifest");
+                span.exit();
+
+                return;
+            }
+        };
+
+        match manifest {
+            Ok(mut manifest) => {
+                let name =
+                    get_manifest_name(&manifest_path, &entry).expect("Failed to get manifest name");
+
+                manifest.root_dir = entry.parent().map(|parent| parent.to_path_buf());
+
+                manifest.name = Some(name.clone());
+
+                manifests.insert(name, manifest);
+            }
+            Err(err) => {
+                let manifest_name = get_manifest_name(&manifest_path, &entry).unwrap_or_default();
+
+                error!("Manifest '{manifest_name}' in file with path '{}' cannot be parsed. Reason: {err}", &entry.display());
+            }
+        }
+
+        span.exit();
+    });
 
     manifests
 }
+
+/// The declarations of every manifest, by manifest name. Manifests can use
+/// the answers to their own prompts, which aren't known yet, so those are
+/// rendered with placeholders, and then with the defaults of the prompts.
+pub fn declarations(
+    manifest_path: PathBuf,
+    contexts: &Contexts,
+) -> anyhow::Result<HashMap<String, Declarations>> {
+    let mut declarations = HashMap::new();
+
+    for entry in manifest_files(&manifest_path) {
+        let Ok(name) = get_manifest_name(&manifest_path, &entry) else {
+            continue;
+        };
+        let Ok(contents) = std::fs::read_to_string(&entry) else {
+            continue;
+        };
+        let yaml = match entry.extension().and_then(OsStr::to_str) {
+            Some("yaml") | Some("yml") => true,
+            Some("toml") => false,
+            _ => continue,
+        };
+
+        let parse = |contents: &str| -> anyhow::Result<Declarations> {
+            Ok(match yaml {
+                true => serde_yaml_ng::from_str(contents)?,
+                false => toml::from_str(contents)?,
+            })
+        };
+
+        let rendered = match render(&contents, contexts) {
+            // Manifests that can't be parsed are reported when they're loaded
+            Ok(rendered) => match parse(&rendered) {
+                Ok(declared) => declared,
+                Err(err) => {
+                    debug!("Skipping declarations of manifest {}: {}", name, err);
+                    continue;
+                }
+            },
+            Err(_) => unanswered_declarations(&contents, contexts, parse).with_context(|| {
+                format!("Can't read the prompts, labels and depends of manifest '{name}'")
+            })?,
+        };
+
+        declarations.insert(name, rendered);
+    }
+
+    Ok(declarations)
+}
+
+/// Reads the declarations of a manifest using prompts that aren't answered
+/// yet, first with empty answers and then with the defaults it declares
+fn unanswered_declarations(
+    contents: &str,
+    contexts: &Contexts,
+    parse: impl Fn(&str) -> anyhow::Result<Declarations>,
+) -> anyhow::Result<Declarations> {
+    let answered = contexts.get("prompts").cloned().unwrap_or_default();
+    let mut seeded = contexts.clone();
+    let placeholders = seeded.entry(String::from("prompts")).or_default();
+
+    for name in prompt_names(contents) {
+        placeholders.entry(name).or_insert_with(|| Value::from(""));
+    }
+
+    let declared = parse(&render(contents, &seeded)?)?;
+    let defaults = seeded.entry(String::from("prompts")).or_default();
+
+    for (name, prompt) in declared.prompts.iter() {
+        if let (false, Some(default)) = (answered.contains_key(name), &prompt.default) {
+            let default = prompt.validate(default).unwrap_or_else(|_| default.clone());
+            defaults.insert(name.clone(), typed(prompt.kind, default));
+        }
+    }
+
+    parse(&render(contents, &seeded)?)
+}
+
+/// The names of the prompts a template uses, like `prompts.name`
+fn prompt_names(contents: &str) -> Vec<String> {
+    let pattern =
+        Regex::new(r#"\bprompts\s*(?:\.\s*([A-Za-z_][A-Za-z0-9_]*)|\[\s*["']([^"']+)["']\s*\])"#)
+            .expect("valid prompt name pattern");
+
+    pattern
+        .captures_iter(contents)
+        .filter_map(|captures| captures.get(1).or_else(|| captures.get(2)))
+        .map(|name| name.as_str().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tera_functions::load_templates;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn it_renders_manifests_with_shared_templates() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+
+        std::fs::create_dir_all(root.join("templates/shell"))?;
+        std::fs::write(
+            root.join("templates/base.yaml"),
+            "actions:\n{% block actions %}{% endblock actions %}",
+        )?;
+        std::fs::write(
+            root.join("templates/shell/macros.yaml"),
+            "{% macro echo(text) %}  - action: command.run\n    command: echo\n    args: [\"{{ text }}\"]\n{% endmacro echo %}",
+        )?;
+        std::fs::write(
+            root.join("templates/shell/greeting.txt"),
+            "Hello {{ variables.planet }}",
+        )?;
+
+        std::fs::write(
+            root.join("greet.yaml"),
+            r#"{% extends "base.yaml" %}
+{% import "shell/macros.yaml" as shell %}
+{% block actions %}{{ shell::echo(text=variables.planet) }}
+  - action: command.run
+    command: echo
+    args: ["{% include "shell/greeting.txt" %}"]
+{% endblock actions %}"#,
+        )?;
+
+        let mut variables = BTreeMap::new();
+        variables.insert(String::from("planet"), Value::from("Abydos"));
+        let mut contexts = Contexts::new();
+        contexts.insert(String::from("variables"), variables);
+
+        load_templates(&root.join("templates"))?;
+        let rendered = render(
+            &std::fs::read_to_string(root.join("greet.yaml"))?,
+            &contexts,
+        )?;
+        let manifests = load(root.to_path_buf(), &contexts);
+
+        assert_eq!(vec!["greet"], manifests.keys().collect::<Vec<_>>());
+        assert_eq!(2, manifests["greet"].actions.len());
+        assert!(rendered.contains(r#"args: ["Abydos"]"#), "{rendered}");
+        assert!(rendered.contains(r#"args: ["Hello Abydos"]"#), "{rendered}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reads_declarations_of_manifests_using_their_prompts() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+
+        std::fs::write(
+            root.join("gate.yaml"),
+            r#"labels: [sgc]
+depends: [./dhd{% if prompts["address"] == "Abydos" %}, ./abydos{% endif %}]
+prompts:
+  address:
+    message: Where to?
+    default: "{{ variables.planet }}"
+actions:
+{% if prompts.address == "Abydos" %}
+  - action: command.run
+    command: echo
+    args: ["{{ prompts.address }}"]
+{% endif %}
+"#,
+        )?;
+        std::fs::write(root.join("dhd.yaml"), "labels: [sgc]\n")?;
+
+        let mut variables = BTreeMap::new();
+        variables.insert(String::from("planet"), Value::from("Abydos"));
+        let mut contexts = Contexts::new();
+        contexts.insert(String::from("variables"), variables);
+
+        let declarations = declarations(root.to_path_buf(), &contexts)?;
+
+        let gate = &declarations["gate"];
+        assert_eq!(vec!["sgc"], gate.labels);
+        assert_eq!(vec!["./dhd", "./abydos"], gate.depends);
+        assert_eq!(Some("Abydos"), gate.prompts["address"].default.as_deref());
+        assert_eq!(vec!["sgc"], declarations["dhd"].labels);
+        assert!(!load(root.to_path_buf(), &contexts).contains_key("gate"));
+
+        // Declarations that can't be read aren't left out silently
+        std::fs::write(
+            root.join("dhd.yaml"),
+            "labels: [sgc]\n{{ prompts.chevrons + 1 }}\n",
+        )?;
+        assert!(super::declarations(root.to_path_buf(), &contexts).is_err());
+
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 mod load;
-pub use load::load;
+pub use load::{declarations, load};
 mod providers;
 use crate::actions::Actions;
+use crate::contexts::prompts::Prompt;
 use petgraph::prelude::*;
 pub use providers::register_providers;
 pub use providers::ManifestProvider;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use tracing::error;
 
@@ -27,6 +29,14 @@ pub struct Manifest {
     #[serde(default)]
     pub actions: Vec<Actions>,
 
+    /// Values asked for once per machine, before any manifest is applied
+    #[serde(default)]
+    pub prompts: BTreeMap<String, Prompt>,
+
+    /// Undo the changes of earlier steps when a step fails
+    #[serde(default)]
+    pub atomic: bool,
+
     #[serde(skip)]
     pub root_dir: Option<PathBuf>,
 
@@ -34,6 +44,20 @@ pub struct Manifest {
     pub dag_index: Option<NodeIndex<u32>>,
 }
 
+/// What must be known of a manifest before it's rendered: its prompts, and
+/// the labels and dependencies that decide whether they're asked
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Declarations {
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub depends: Vec<String>,
+
+    #[serde(default)]
+    pub prompts: BTreeMap<String, Prompt>,
+}
+
 pub fn resolve(uri: &String) -> Option<PathBuf> {
     let manifest_directory = register_providers()
         .into_iter()
@@ -0,0 +1,28 @@
+use super::{run, SecretBackend};
+use anyhow::Result;
+
+/// `bw get password <path>` of the Bitwarden CLI, which must be unlocked
+/// with `BW_SESSION` set
+pub struct Bitwarden {
+    pub name: String,
+    pub program: String,
+}
+
+impl Default for Bitwarden {
+    fn default() -> Self {
+        Bitwarden {
+            name: String::from("bw"),
+            program: String::from("bw"),
+        }
+    }
+}
+
+impl SecretBackend for Bitwarden {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, path: &str) -> Result<String> {
+        run(&self.program, &["get", "password", path])
+    }
+}
@@ -0,0 +1,27 @@
+use super::{run, SecretBackend};
+use anyhow::Result;
+
+/// `gopass show --password <path>`
+pub struct Gopass {
+    pub name: String,
+    pub program: String,
+}
+
+impl Default for Gopass {
+    fn default() -> Self {
+        Gopass {
+            name: String::from("gopass"),
+            program: String::from("gopass"),
+        }
+    }
+}
+
+impl SecretBackend for Gopass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, path: &str) -> Result<String> {
+        run(&self.program, &["show", "--password", path])
+    }
+}
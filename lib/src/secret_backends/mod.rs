@@ -0,0 +1,187 @@
+mod bitwarden;
+use self::bitwarden::Bitwarden;
+mod gopass;
+use self::gopass::Gopass;
+mod onepassword;
+use self::onepassword::OnePassword;
+mod pass;
+use self::pass::Pass;
+
+use crate::contexts::secrets::register_secret;
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use tracing::debug;
+
+/// A password manager secrets can be read from
+pub trait SecretBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The secret at `path`, without a trailing newline
+    fn get(&self, path: &str) -> Result<String>;
+}
+
+/// A secret of a backend, e.g. for `passphrase_from` of `file.copy`
+#[derive(JsonSchema, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretRef {
+    pub backend: String,
+    pub path: String,
+}
+
+impl SecretRef {
+    pub fn get(&self) -> Result<String> {
+        secret(&self.backend, &self.path)
+    }
+}
+
+static BACKENDS: LazyLock<RwLock<BTreeMap<String, Arc<dyn SecretBackend>>>> = LazyLock::new(|| {
+    let backends: Vec<Arc<dyn SecretBackend>> = vec![
+        Arc::new(Pass::default()),
+        Arc::new(Gopass::default()),
+        Arc::new(OnePassword::default()),
+        Arc::new(Bitwarden::default()),
+    ];
+
+    RwLock::new(
+        backends
+            .into_iter()
+            .map(|backend| (backend.name().to_string(), backend))
+            .collect(),
+    )
+});
+
+/// Secrets read during this run, by backend and path
+static CACHE: LazyLock<Mutex<BTreeMap<(String, String), String>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Adds a backend, replacing a backend with the same name
+pub fn register_backend(backend: impl SecretBackend + 'static) {
+    BACKENDS
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(backend.name().to_string(), Arc::new(backend));
+}
+
+/// Reads a secret from a backend, at most once per run. The value is
+/// registered for redaction.
+pub fn secret(backend: &str, path: &str) -> Result<String> {
+    let key = (backend.to_string(), path.to_string());
+
+    if let Some(value) = CACHE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(&key)
+    {
+        return Ok(value.clone());
+    }
+
+    let provider = BACKENDS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(backend)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown secret backend '{backend}'"))?;
+
+    debug!("Reading secret {} from {}", path, backend);
+
+    let value = provider
+        .get(path)
+        .with_context(|| format!("Failed to read secret '{path}' from {backend}"))?;
+
+    register_secret(&value);
+
+    CACHE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(key, value.clone());
+
+    Ok(value)
+}
+
+/// Runs a password manager CLI, returning its stdout without the trailing
+/// newline. Stdout isn't part of errors, as it may hold the secret.
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run '{program}'"))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'{program}' exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8(output.stdout)
+        .with_context(|| format!("'{program}' printed invalid UTF-8"))?
+        .trim_end_matches(['\r', '\n'])
+        .to_string())
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+pub(crate) mod tests {
+    pub(crate) use super::pass::Pass;
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+
+    /// Writes an executable script standing in for a password manager CLI
+    pub(crate) fn stub(dir: &Path, name: &str, script: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn it_reads_secrets_from_command_backends() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let calls = dir.path().join("calls");
+
+        let pass = stub(
+            dir.path(),
+            "pass",
+            &format!(
+                r#"echo "$@" >> {}
+[ "$2" = "stargate/sgc" ] || {{ echo "Error: $2 is not in the password store." >&2; exit 1; }}
+printf 'kree-sha-token\nlogin: jack\n'"#,
+                calls.display()
+            ),
+        );
+
+        register_backend(Pass {
+            name: String::from("pass-stub"),
+            program: pass.display().to_string(),
+        });
+
+        assert_eq!("kree-sha-token", secret("pass-stub", "stargate/sgc")?);
+        assert_eq!("kree-sha-token", secret("pass-stub", "stargate/sgc")?);
+        assert_eq!("show stargate/sgc\n", std::fs::read_to_string(&calls)?);
+
+        let err = format!(
+            "{:#}",
+            secret("pass-stub", "stargate/atlantis").unwrap_err()
+        );
+        assert!(err.contains("not in the password store"), "{err}");
+
+        assert!(secret("keepass", "stargate/sgc").is_err());
+
+        let rendered = crate::tera_functions::render(
+            r#"token={{ secret(backend="pass-stub", path="stargate/sgc") }}"#,
+            &crate::contexts::Contexts::new(),
+        )?;
+        assert_eq!("token=kree-sha-token", rendered);
+        // The cached secret and the failed lookup
+        assert_eq!(2, std::fs::read_to_string(&calls)?.lines().count());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use super::{run, SecretBackend};
+use anyhow::Result;
+
+/// `op read <path>` of the 1Password CLI, paths are secret references like
+/// `op://vault/item/field`
+pub struct OnePassword {
+    pub name: String,
+    pub program: String,
+}
+
+impl Default for OnePassword {
+    fn default() -> Self {
+        OnePassword {
+            name: String::from("op"),
+            program: String::from("op"),
+        }
+    }
+}
+
+impl SecretBackend for OnePassword {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, path: &str) -> Result<String> {
+        run(&self.program, &["read", "--no-newline", path])
+    }
+}
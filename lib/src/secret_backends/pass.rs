@@ -0,0 +1,29 @@
+use super::{run, SecretBackend};
+use anyhow::Result;
+
+/// `pass show <path>`, the first line of the entry
+pub struct Pass {
+    pub name: String,
+    pub program: String,
+}
+
+impl Default for Pass {
+    fn default() -> Self {
+        Pass {
+            name: String::from("pass"),
+            program: String::from("pass"),
+        }
+    }
+}
+
+impl SecretBackend for Pass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, path: &str) -> Result<String> {
+        let output = run(&self.program, &["show", path])?;
+
+        Ok(output.lines().next().unwrap_or_default().to_string())
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::path::Path;
+use tera::{Result, Value};
+
+fn path<'a>(filter: &str, value: &'a Value) -> Result<&'a Path> {
+    value
+        .as_str()
+        .map(Path::new)
+        .ok_or_else(|| format!("Filter `{filter}`: expected a path, got '{value}'").into())
+}
+
+/// `{{ "/etc/ssh/sshd_config" | basename }}` -> `sshd_config`
+pub fn basename(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    Ok(path("basename", value)?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .into())
+}
+
+/// `{{ "/etc/ssh/sshd_config" | dirname }}` -> `/etc/ssh`
+pub fn dirname(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    Ok(path("dirname", value)?
+        .parent()
+        .map(|parent| parent.display().to_string())
+        .unwrap_or_default()
+        .into())
+}
+
+/// `{{ value | to_json(pretty=true) }}`
+pub fn to_json(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let pretty = args.get("pretty").and_then(Value::as_bool).unwrap_or(false);
+
+    let json = match pretty {
+        true => serde_json::to_string_pretty(value),
+        false => serde_json::to_string(value),
+    };
+
+    json.map(Into::into)
+        .map_err(|err| format!("Filter `to_json`: {err}").into())
+}
+
+pub fn to_yaml(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    serde_yaml_ng::to_string(value)
+        .map(Into::into)
+        .map_err(|err| format!("Filter `to_yaml`: {err}").into())
+}
+
+/// Only maps can be serialized, as TOML documents are tables
+pub fn to_toml(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    toml::to_string(value)
+        .map(Into::into)
+        .map_err(|err| format!("Filter `to_toml`: {err}").into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::contexts::Contexts;
+    use crate::tera_functions::render;
+    use crate::values::Value;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn can_serialize_values() -> anyhow::Result<()> {
+        let mut variables = BTreeMap::new();
+        variables.insert(String::from("planet"), Value::from("Abydos"));
+        variables.insert(String::from("gates"), Value::from(vec!["Chulak", "Dakara"]));
+
+        let mut contexts = Contexts::new();
+        contexts.insert(String::from("variables"), variables);
+
+        assert_eq!(
+            r#"{"gates":["Chulak","Dakara"],"planet":"Abydos"}"#,
+            render("{{ variables | to_json }}", &contexts)?
+        );
+        assert_eq!(
+            "gates:\n- Chulak\n- Dakara\nplanet: Abydos\n",
+            render("{{ variables | to_yaml }}", &contexts)?
+        );
+        assert_eq!(
+            "gates = [\"Chulak\", \"Dakara\"]\nplanet = \"Abydos\"\n",
+            render("{{ variables | to_toml }}", &contexts)?
+        );
+        assert!(render("{{ variables.planet | to_toml }}", &contexts).is_err());
+
+        Ok(())
+    }
+}
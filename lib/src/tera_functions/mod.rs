@@ -1,4 +1,16 @@
+use crate::contexts::{to_tera, Contexts};
+use globset::GlobBuilder;
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
 use tera::{Function, Result, Tera, Value};
+use walkdir::WalkDir;
+
+mod filters;
+mod templates;
+
+pub use templates::{load_templates, templates_dir};
 
 pub struct ReadFileContents;
 
@@ -24,11 +36,219 @@ impl Function for ReadFileContents {
 
 pub fn register_functions(tera: &mut Tera) {
     tera.register_function("read_file_contents", ReadFileContents);
+    tera.register_function("env", env);
+    tera.register_function("command_output", command_output);
+    tera.register_function("file_exists", file_exists);
+    tera.register_function("glob", glob);
+    tera.register_function("sha256", sha256);
+    tera.register_function("path_join", path_join);
+    tera.register_function("which", which);
+    tera.register_function("secret", secret);
+
+    tera.register_filter("basename", filters::basename);
+    tera.register_filter("dirname", filters::dirname);
+    tera.register_filter("to_json", filters::to_json);
+    tera.register_filter("to_yaml", filters::to_yaml);
+    tera.register_filter("to_toml", filters::to_toml);
+}
+
+/// Renders `template` with every function and filter registered, the loaded
+/// templates available and the contexts as variables. Errors include every
+/// cause reported by Tera.
+pub fn render(template: &str, contexts: &Contexts) -> anyhow::Result<String> {
+    templates::with_tera(|tera| tera.render_str(template, &to_tera(contexts))).map_err(|err| {
+        let mut message = err.to_string();
+        let mut source = err.source();
+
+        while let Some(cause) = source {
+            message.push_str(&format!(": {cause}"));
+            source = cause.source();
+        }
+
+        anyhow::anyhow!(message)
+    })
+}
+
+fn string_arg(function: &str, args: &HashMap<String, Value>, name: &str) -> Result<String> {
+    match args.get(name) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(value) => Err(format!(
+            "Function `{function}`: argument '{name}' must be a string, got '{value}'"
+        )
+        .into()),
+        None => Err(format!("Function `{function}`: argument '{name}' not set").into()),
+    }
+}
+
+fn string_list_arg(
+    function: &str,
+    args: &HashMap<String, Value>,
+    name: &str,
+) -> Result<Vec<String>> {
+    match args.get(name) {
+        None => Ok(vec![]),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(value) => Ok(value.clone()),
+                value => Ok(value.to_string()),
+            })
+            .collect(),
+        Some(value) => Err(format!(
+            "Function `{function}`: argument '{name}' must be a list, got '{value}'"
+        )
+        .into()),
+    }
+}
+
+/// `env(name="HOME", default="/root")`, errors if unset and without default
+fn env(args: &HashMap<String, Value>) -> Result<Value> {
+    let name = string_arg("env", args, "name")?;
+
+    match (std::env::var(&name), args.get("default")) {
+        (Ok(value), _) => Ok(value.into()),
+        (Err(_), Some(default)) => Ok(default.clone()),
+        (Err(err), None) => Err(format!("Function `env`: variable '{name}': {err}").into()),
+    }
+}
+
+/// `command_output(cmd="git", args=["config", "user.name"])`, the trimmed stdout
+fn command_output(args: &HashMap<String, Value>) -> Result<Value> {
+    let cmd = string_arg("command_output", args, "cmd")?;
+    let cmd_args = string_list_arg("command_output", args, "args")?;
+
+    let output = Command::new(&cmd)
+        .args(&cmd_args)
+        .output()
+        .map_err(|err| format!("Function `command_output`: failed to run '{cmd}': {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Function `command_output`: '{cmd}' exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().into())
+}
+
+/// `file_exists(path="/etc/hosts")`
+fn file_exists(args: &HashMap<String, Value>) -> Result<Value> {
+    let path = string_arg("file_exists", args, "path")?;
+
+    Ok(Path::new(&path).exists().into())
+}
+
+/// `glob(pattern="/etc/*.conf")`, the sorted matching paths
+fn glob(args: &HashMap<String, Value>) -> Result<Value> {
+    let pattern = string_arg("glob", args, "pattern")?;
+
+    // `*` stays within a directory, only `**` crosses into others
+    let matcher = GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| format!("Function `glob`: invalid pattern '{pattern}': {err}"))?
+        .compile_matcher();
+
+    // Only walk below the part of the pattern without wildcards
+    let base: PathBuf = Path::new(&pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '[', '{'])
+        })
+        .collect();
+
+    let recursive = Path::new(&pattern)
+        .components()
+        .any(|component| component == Component::Normal("**".as_ref()));
+
+    let depth = if recursive {
+        usize::MAX
+    } else {
+        Path::new(&pattern).components().count() - base.components().count()
+    };
+
+    let root = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    };
+
+    let mut paths: Vec<String> = WalkDir::new(&root)
+        .max_depth(depth)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .map(|path| {
+            path.strip_prefix("./")
+                .map(Path::to_path_buf)
+                .unwrap_or(path)
+        })
+        .filter(|path| matcher.is_match(path))
+        .map(|path| path.display().to_string())
+        .collect();
+
+    paths.sort();
+
+    Ok(paths.into())
+}
+
+/// `sha256(path="file")` hashes a file, `sha256(string="text")` a string
+fn sha256(args: &HashMap<String, Value>) -> Result<Value> {
+    match (args.get("path"), args.get("string")) {
+        (Some(_), None) => {
+            let path = string_arg("sha256", args, "path")?;
+
+            sha256::try_digest(Path::new(&path))
+                .map(Into::into)
+                .map_err(|err| format!("Function `sha256`: failed to read '{path}': {err}").into())
+        }
+        (None, Some(_)) => Ok(sha256::digest(string_arg("sha256", args, "string")?).into()),
+        _ => Err("Function `sha256`: exactly one of 'path' or 'string' must be set".into()),
+    }
+}
+
+/// `path_join(parts=[user.home_dir, ".config", "nvim"])`
+fn path_join(args: &HashMap<String, Value>) -> Result<Value> {
+    let parts = string_list_arg("path_join", args, "parts")?;
+
+    if parts.is_empty() {
+        return Err("Function `path_join`: argument 'parts' not set or empty".into());
+    }
+
+    let path: PathBuf = parts.iter().collect();
+
+    Ok(path.display().to_string().into())
+}
+
+/// `which(binary="git")`, the absolute path of a binary in `PATH`
+fn which(args: &HashMap<String, Value>) -> Result<Value> {
+    let binary = string_arg("which", args, "binary")?;
+
+    which::which(&binary)
+        .map(|path| path.display().to_string().into())
+        .map_err(|err| format!("Function `which`: '{binary}': {err}").into())
+}
+
+/// `secret(backend="pass", path="email/github")`, read once per run
+fn secret(args: &HashMap<String, Value>) -> Result<Value> {
+    let backend = string_arg("secret", args, "backend")?;
+    let path = string_arg("secret", args, "path")?;
+
+    crate::secret_backends::secret(&backend, &path)
+        .map(Into::into)
+        .map_err(|err| format!("Function `secret`: {err:#}").into())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use pretty_assertions::assert_eq;
     use std::io::Write;
     use tera::{Context, Tera};
 
@@ -63,4 +283,56 @@ SXOE"#;
 
         Ok(())
     }
+
+    #[test]
+    fn can_use_path_and_file_functions() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.conf"), "abc")?;
+        std::fs::write(dir.path().join("b.conf"), "")?;
+        std::fs::write(dir.path().join("c.txt"), "")?;
+        std::fs::create_dir(dir.path().join("ssh"))?;
+        std::fs::write(dir.path().join("ssh").join("d.conf"), "")?;
+
+        let dir = dir.path().display();
+        let template = format!(
+            r#"{{{{ glob(pattern="{dir}/*.conf") | length }}}}
+{{% for path in glob(pattern="{dir}/*.conf") %}}{{{{ path | basename }}}} {{% endfor %}}
+{{{{ glob(pattern="{dir}/**/*.conf") | length }}}}
+{{{{ file_exists(path="{dir}/c.txt") }}}} {{{{ file_exists(path="{dir}/d.txt") }}}}
+{{{{ sha256(path="{dir}/a.conf") == sha256(string="abc") }}}}
+{{{{ path_join(parts=["/etc", "ssh", "sshd_config"]) | dirname }}}}"#
+        );
+
+        let rendered = render(&template, &Contexts::new())?;
+
+        assert_eq!("2\na.conf b.conf \n3\ntrue false\ntrue\n/etc/ssh", rendered);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn can_use_env_and_commands() -> anyhow::Result<()> {
+        let rendered = render(
+            r#"{{ env(name="COMTRYA_DOES_NOT_EXIST", default="fallback") }} {{ command_output(cmd="echo", args=["hello", 42]) }} {{ which(binary="sh") | basename }}"#,
+            &Contexts::new(),
+        )?;
+
+        assert_eq!("fallback hello 42 sh", rendered);
+
+        let err = render(
+            r#"{{ env(name="COMTRYA_DOES_NOT_EXIST") }}"#,
+            &Contexts::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("COMTRYA_DOES_NOT_EXIST"), "{err}");
+
+        let err = render("{{ command_output() }}", &Contexts::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("argument 'cmd' not set"), "{err}");
+
+        Ok(())
+    }
 }
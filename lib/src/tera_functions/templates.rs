@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tera::Tera;
+use tracing::debug;
+
+/// Templates that manifests and templated files can include, import macros
+/// from and extend, by their path relative to the templates directory.
+struct Templates {
+    dir: PathBuf,
+    tera: Mutex<Tera>,
+}
+
+static TEMPLATES: OnceLock<Templates> = OnceLock::new();
+
+/// Loads every file below `dir` as a template, once per run. Loading the
+/// same directory again does nothing, a missing directory loads nothing.
+pub fn load_templates(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve templates at {}", dir.display()))?;
+
+    if let Some(loaded) = TEMPLATES.get() {
+        return match loaded.dir == dir {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "Templates are already loaded from {}",
+                loaded.dir.display()
+            )),
+        };
+    }
+
+    let glob = format!("{}/**/*", dir.display());
+    let mut tera = Tera::new(&glob)
+        .with_context(|| format!("Failed to load templates from {}", dir.display()))?;
+
+    // Manifests and configuration files must not be HTML escaped
+    tera.autoescape_on(vec![]);
+    super::register_functions(&mut tera);
+
+    debug!(
+        "Loaded {} templates from {}",
+        tera.get_template_names().count(),
+        dir.display()
+    );
+
+    // Loaded concurrently, the templates are the same either way
+    let _ = TEMPLATES.set(Templates {
+        dir,
+        tera: Mutex::new(tera),
+    });
+
+    Ok(())
+}
+
+/// The directory templates were loaded from
+pub fn templates_dir() -> Option<PathBuf> {
+    TEMPLATES.get().map(|templates| templates.dir.clone())
+}
+
+/// Calls `f` with the instance holding the loaded templates, or with a new
+/// instance when none were loaded
+pub(crate) fn with_tera<T>(f: impl FnOnce(&mut Tera) -> T) -> T {
+    match TEMPLATES.get() {
+        Some(templates) => f(&mut templates.tera.lock().unwrap_or_else(|err| err.into_inner())),
+        None => {
+            let mut tera = Tera::default();
+            super::register_functions(&mut tera);
+
+            f(&mut tera)
+        }
+    }
+}
@@ -1,5 +1,9 @@
 pub mod lua;
-use std::{borrow::Cow, ops::Deref, path::PathBuf};
+use std::{
+    borrow::Cow,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
 
 use crate::contexts::Contexts;
 
@@ -8,6 +12,59 @@ use schemars::{JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
 use which::which;
 
+/// Writes a file only the current user can read, for files that may hold
+/// secrets
+pub(crate) fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+
+        // The mode only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(path)?.write_all(contents.as_ref())?;
+
+    Ok(())
+}
+
+/// Removes a file, link or directory with everything in it
+pub(crate) fn remove(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path)?.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    }
+}
+
+/// Creates a symlink at `path` pointing to `target`. On Windows a relative
+/// `target` is resolved against the link's directory to pick the link kind.
+#[cfg(unix)]
+pub(crate) fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+/// Creates a symlink at `path` pointing to `target`. On Windows a relative
+/// `target` is resolved against the link's directory to pick the link kind.
+#[cfg(windows)]
+pub(crate) fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    match path.parent().unwrap_or(Path::new("")).join(target).is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, path),
+        false => std::os::windows::fs::symlink_file(target, path),
+    }
+}
+
 pub fn get_binary_path(binary: &str) -> Result<String, anyhow::Error> {
     let binary = which(binary)?.to_string_lossy().to_string();
 